@@ -1,4 +1,5 @@
 use crate::filter::FilterOptions;
+use crate::import::OnConflict;
 use crate::shell::{self, Shell};
 use crate::Context;
 use crate::{cli::utilities::get_input, target::Target};
@@ -6,7 +7,9 @@ use crate::{filter::parse_match_option, output::Output};
 use anyhow::Result;
 use etcetera::base_strategy::{BaseStrategy, Xdg};
 
-use super::shape::{CliOptions, KeyValuePair, SetKinds, ShellKinds, ShowKinds, SubCommand};
+use super::shape::{
+    CliOptions, KeyValuePair, SetKinds, ShellKinds, ShowKinds, SubCommand, UnsetKinds,
+};
 
 pub fn run() -> Result<()> {
     let opts: CliOptions = CliOptions::parse();
@@ -74,7 +77,12 @@ pub fn match_subcommmand(
             };
         }
         Some(SubCommand::Config(cmd)) => {
-            context.set_config(&cmd.values, cmd.global, output);
+            if !cmd.values.is_empty() || !cmd.show_resolved {
+                context.set_config(&cmd.values, cmd.global, output);
+            }
+            if cmd.show_resolved {
+                context.show_settings(output);
+            }
         }
         Some(SubCommand::Move(cmd)) => context.move_saucefile(output, &cmd.destination, cmd.copy),
         Some(SubCommand::New) => context.create_saucefile(output),
@@ -87,13 +95,42 @@ pub fn match_subcommmand(
             SetKinds::File(KeyValuePair { key, value }) => context.set_file(key, value, output),
         },
         Some(SubCommand::Edit) => context.edit_saucefile(shell_kind, output),
+        Some(SubCommand::Run(cmd)) => match &cmd.function {
+            Some(function) if !cmd.list => {
+                context.run_function(shell_kind, function, &cmd.args, output)
+            }
+            _ => context.list_functions(output),
+        },
         Some(SubCommand::Show(show)) => match show.kind {
-            ShowKinds::Env => context.show(Target::EnvVar, output),
-            ShowKinds::Function => context.show(Target::Function, output),
-            ShowKinds::Alias => context.show(Target::Alias, output),
-            ShowKinds::File => context.show(Target::File, output),
+            ShowKinds::Env => context.show(Target::EnvVar, show.origin, output),
+            ShowKinds::Function => context.show(Target::Function, show.origin, output),
+            ShowKinds::Alias => context.show(Target::Alias, show.origin, output),
+            ShowKinds::File => context.show(Target::File, show.origin, output),
+            ShowKinds::Path => context.show(Target::Path, show.origin, output),
         },
         Some(SubCommand::Clear) => context.clear(shell_kind, output),
+        Some(SubCommand::Status) => context.status(output),
+        Some(SubCommand::Unset(cmd)) => match &cmd.kind {
+            UnsetKinds::Env(env) => context.unset_values(Target::EnvVar, &env.keys, output),
+            UnsetKinds::Alias(alias) => context.unset_values(Target::Alias, &alias.keys, output),
+            UnsetKinds::Function(function) => {
+                context.unset_values(Target::Function, &function.keys, output)
+            }
+            UnsetKinds::File(file) => context.unset_values(Target::File, &file.keys, output),
+        },
+        Some(SubCommand::Exec(cmd)) => context.exec(&cmd.command, cmd.pure, output),
+        Some(SubCommand::Export(cmd)) => context.export(cmd.format, cmd.output.as_deref(), output),
+        Some(SubCommand::Import(cmd)) => {
+            let on_conflict = if cmd.overwrite {
+                OnConflict::Overwrite
+            } else if cmd.skip {
+                OnConflict::Skip
+            } else {
+                OnConflict::Abort
+            };
+            context.import(&cmd.file, on_conflict, output)
+        }
+        Some(SubCommand::Which(cmd)) => context.which(&cmd.key, cmd.json, output),
         None => context.execute(shell_kind, autoload, output),
     };
 }
@@ -1,3 +1,4 @@
+use crate::export::ExportFormat;
 use crate::shell::{ColorStrategy, ShellName};
 use clap::Parser;
 use std::{io::Write, path::PathBuf};
@@ -7,7 +8,7 @@ use std::{io::Write, path::PathBuf};
 #[command(version, author)]
 pub struct CliOptions {
     /// Determines the shell behavior, this flag should always be set automatically
-    /// by the shell hook. Valid options are: zsh, fish, bash
+    /// by the shell hook. Valid options are: zsh, fish, bash, nu, pwsh
     #[arg(long)]
     pub shell: ShellName,
 
@@ -103,8 +104,30 @@ pub enum SubCommand {
     /// Group of shell related subcommands
     Shell(ShellCommand),
 
+    /// Run a saucefile function as a task, in a subshell with the sauced environment
+    Run(RunCommand),
+
     /// Display the given category of key-value pairs
     Show(ShowCommand),
+
+    /// Display what is currently sauced, and whether any of it has since changed
+    Status,
+
+    /// Removes target values from the targeted location
+    #[command(alias = "remove")]
+    Unset(UnsetCommand),
+
+    /// Run a command directly, with the sauced environment applied to it
+    Exec(ExecCommand),
+
+    /// Export the resolved values to another format, i.e. for docker or CI
+    Export(ExportCommand),
+
+    /// Import env vars and aliases from a `.env` (or `.envrc`-style) file
+    Import(ImportCommand),
+
+    /// Explain how the given key resolves through the cascade of saucefiles
+    Which(WhichCommand),
 }
 
 #[derive(Parser, Debug)]
@@ -112,6 +135,10 @@ pub struct ConfigCommand {
     #[arg(long, short)]
     pub global: bool,
 
+    /// Show the effective value of each setting, and the file which set it
+    #[arg(long)]
+    pub show_resolved: bool,
+
     #[arg(value_parser = crate::cli::utilities::parse_key_val::<String>)]
     pub values: Vec<(String, String)>,
 }
@@ -157,6 +184,27 @@ pub struct KeyValuePair {
     pub value: String,
 }
 
+#[derive(Parser, Debug)]
+pub struct UnsetCommand {
+    #[command(subcommand)]
+    pub kind: UnsetKinds,
+}
+
+#[derive(Parser, Debug)]
+pub enum UnsetKinds {
+    Env(Keys),
+    Alias(Keys),
+    Function(Keys),
+    File(Keys),
+}
+
+/// The keys to remove. With `--as`, only the values for the given tags are removed.
+#[derive(Parser, Debug)]
+pub struct Keys {
+    #[arg(required = true)]
+    pub keys: Vec<String>,
+}
+
 #[derive(Parser, Debug)]
 pub struct ShellCommand {
     #[command(subcommand)]
@@ -169,17 +217,80 @@ pub enum ShellKinds {
     Init,
 
     /// Executes a command inside a subshell which has had `sauce` invoked already
-    Exec(ExecCommand),
+    Exec(ShellExecCommand),
 }
 
 #[derive(Parser, Debug)]
-pub struct ExecCommand {
+pub struct ShellExecCommand {
     #[arg()]
     pub command: String,
 }
 
+#[derive(Parser, Debug)]
+pub struct ExecCommand {
+    /// Start from an empty environment, rather than the current one
+    #[arg(long)]
+    pub pure: bool,
+
+    /// The command (and its arguments) to run, i.e. `sauce exec -- make test`
+    #[arg(required = true, trailing_var_arg = true, allow_hyphen_values = true)]
+    pub command: Vec<String>,
+}
+
+#[derive(Parser, Debug)]
+pub struct ExportCommand {
+    /// Valid options: dotenv, json, yaml, docker, systemd, sh.
+    #[arg(long, default_value = "dotenv")]
+    pub format: ExportFormat,
+
+    /// Write the export to the given file, rather than printing it.
+    #[arg(short, long)]
+    pub output: Option<PathBuf>,
+}
+
+#[derive(Parser, Debug)]
+pub struct ImportCommand {
+    pub file: PathBuf,
+
+    /// Overwrite any values which already exist in the saucefile.
+    #[arg(long, conflicts_with = "skip")]
+    pub overwrite: bool,
+
+    /// Skip any values which already exist in the saucefile.
+    #[arg(long)]
+    pub skip: bool,
+}
+
+#[derive(Parser, Debug)]
+pub struct RunCommand {
+    /// List the functions which can be run
+    #[arg(long, conflicts_with = "function")]
+    pub list: bool,
+
+    #[arg(required_unless_present = "list")]
+    pub function: Option<String>,
+
+    /// The arguments with which to call the function
+    #[arg(trailing_var_arg = true, allow_hyphen_values = true)]
+    pub args: Vec<String>,
+}
+
+#[derive(Parser, Debug)]
+pub struct WhichCommand {
+    pub key: String,
+
+    /// Output the explanation as JSON, rather than a table
+    #[arg(long)]
+    pub json: bool,
+}
+
 #[derive(Parser, Debug)]
 pub struct ShowCommand {
+    /// Include the saucefile (and tag) which supplied each value, along with
+    /// any values it shadowed further up the cascade
+    #[arg(long, global = true)]
+    pub origin: bool,
+
     #[command(subcommand)]
     pub kind: ShowKinds,
 }
@@ -190,4 +301,5 @@ pub enum ShowKinds {
     Alias,
     Function,
     File,
+    Path,
 }
@@ -0,0 +1,245 @@
+use indexmap::IndexMap;
+use std::{fmt::Write, str::FromStr};
+
+use crate::shell::Shell;
+use crate::value::PathEntries;
+
+/// The formats into which the resolved values can be exported.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ExportFormat {
+    /// A `.env` file.
+    Dotenv,
+    Json,
+    Yaml,
+    /// A file for `docker run --env-file`, which supports neither quoting nor
+    /// multiline values.
+    Docker,
+    /// A systemd `environment.d` fragment.
+    Systemd,
+    /// A POSIX `sh` script.
+    Sh,
+}
+
+impl FromStr for ExportFormat {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, String> {
+        match s {
+            "dotenv" => Ok(Self::Dotenv),
+            "json" => Ok(Self::Json),
+            "yaml" => Ok(Self::Yaml),
+            "docker" => Ok(Self::Docker),
+            "systemd" => Ok(Self::Systemd),
+            "sh" => Ok(Self::Sh),
+            unhandled => Err(format!(
+                "Unrecognized format '{}'. Valid options are: dotenv, json, yaml, docker, systemd, sh",
+                unhandled
+            )),
+        }
+    }
+}
+
+/// The resolved values to be exported.
+#[derive(Debug, Default)]
+pub struct Export<'a> {
+    pub vars: Vec<(&'a str, String)>,
+    pub aliases: Vec<(&'a str, String)>,
+    pub functions: Vec<(&'a str, String)>,
+    pub path_entries: Vec<PathEntries<'a>>,
+}
+
+impl<'a> Export<'a> {
+    /// Renders the export in the given `format`, along with the names of any values
+    /// which the format can't represent, and were therefore skipped.
+    ///
+    /// Only `sh` can represent aliases, functions, or `[path]` entries (which extend
+    /// the variable's existing value), so the other formats contain only env vars.
+    pub fn render(&self, format: ExportFormat) -> (String, Vec<&'a str>) {
+        let mut skipped = Vec::new();
+        let mut result = String::new();
+
+        match format {
+            ExportFormat::Dotenv => {
+                for (name, value) in self.vars.iter() {
+                    writeln!(result, "{}={}", name, dotenv_quote(value)).ok();
+                }
+            }
+            ExportFormat::Json => {
+                let vars = self
+                    .vars
+                    .iter()
+                    .map(|(name, value)| (*name, value.as_str()))
+                    .collect::<IndexMap<_, _>>();
+                if let Ok(json) = serde_json::to_string_pretty(&vars) {
+                    writeln!(result, "{}", json).ok();
+                }
+            }
+            ExportFormat::Yaml => {
+                if self.vars.is_empty() {
+                    result.push_str("{}\n");
+                }
+                for (name, value) in self.vars.iter() {
+                    // JSON strings are valid YAML double-quoted scalars.
+                    writeln!(result, "{}: {}", json_quote(name), json_quote(value)).ok();
+                }
+            }
+            ExportFormat::Docker => {
+                for (name, value) in self.vars.iter() {
+                    if value.contains('\n') {
+                        skipped.push(*name);
+                    } else {
+                        writeln!(result, "{}={}", name, value).ok();
+                    }
+                }
+            }
+            ExportFormat::Systemd => {
+                for (name, value) in self.vars.iter() {
+                    if value.contains('\n') {
+                        skipped.push(*name);
+                    } else {
+                        writeln!(result, "{}={}", name, systemd_quote(value)).ok();
+                    }
+                }
+            }
+            ExportFormat::Sh => {
+                let shell = Sh;
+                for (name, value) in self.vars.iter() {
+                    writeln!(result, "{}", shell.set_var(name, value)).ok();
+                }
+                for p in self.path_entries.iter() {
+                    writeln!(result, "{}", shell.add_path(p.var, p.position, &p.entries)).ok();
+                }
+                for (name, value) in self.aliases.iter() {
+                    writeln!(result, "{}", shell.set_alias(name, value)).ok();
+                }
+                for (name, value) in self.functions.iter() {
+                    writeln!(result, "{}", shell.set_function(name, value)).ok();
+                }
+                return (result, skipped);
+            }
+        }
+
+        skipped.extend(self.aliases.iter().map(|(name, _)| *name));
+        skipped.extend(self.functions.iter().map(|(name, _)| *name));
+        skipped.extend(self.path_entries.iter().map(|p| p.var));
+        (result, skipped)
+    }
+}
+
+/// A POSIX `sh`, as written by the [`Shell`] defaults.
+struct Sh;
+
+impl Shell for Sh {
+    fn name(&self) -> &'static str {
+        "sh"
+    }
+
+    fn init(&self, _binary: &str, _autoload: bool, _autoload_args: &str) -> String {
+        String::new()
+    }
+
+    // An exported script is only ever run, so nothing it defines is restored.
+    fn save_function(&self, _var: &str) -> String {
+        String::new()
+    }
+}
+
+/// Single quotes (which are taken literally) where possible, falling back to double
+/// quotes (which support escapes) for values containing single quotes or newlines.
+fn dotenv_quote(value: &str) -> String {
+    if !value.contains('\'') && !value.contains('\n') {
+        return format!("'{}'", value);
+    }
+
+    let escaped = value
+        .replace('\\', "\\\\")
+        .replace('"', "\\\"")
+        .replace('$', "\\$")
+        .replace('\n', "\\n");
+    format!("\"{}\"", escaped)
+}
+
+/// Escapes the characters which `environment.d` would otherwise expand.
+fn systemd_quote(value: &str) -> String {
+    value.replace('\\', "\\\\").replace('$', "\\$")
+}
+
+fn json_quote(value: &str) -> String {
+    serde_json::Value::from(value).to_string()
+}
+
+#[cfg(test)]
+mod tests {
+    mod render {
+        use super::super::*;
+        use crate::value::PathPosition;
+        use pretty_assertions::assert_eq;
+        use std::path::Path;
+
+        fn export() -> Export<'static> {
+            Export {
+                vars: vec![
+                    ("FOO", "foo".to_string()),
+                    ("BAR", "it's\n$HOME".to_string()),
+                ],
+                aliases: vec![("g", "git".to_string())],
+                functions: vec![],
+                path_entries: vec![PathEntries {
+                    origin: Path::new("a.toml"),
+                    var: "PATH",
+                    position: PathPosition::Prepend,
+                    entries: vec!["/a/bin".to_string()],
+                }],
+            }
+        }
+
+        #[test]
+        fn it_renders_dotenv() {
+            let (result, skipped) = export().render(ExportFormat::Dotenv);
+            assert_eq!(result, "FOO='foo'\nBAR=\"it's\\n\\$HOME\"\n");
+            assert_eq!(skipped, vec!["g", "PATH"]);
+        }
+
+        #[test]
+        fn it_renders_json() {
+            let (result, _) = export().render(ExportFormat::Json);
+            assert_eq!(
+                result,
+                "{\n  \"FOO\": \"foo\",\n  \"BAR\": \"it's\\n$HOME\"\n}\n"
+            );
+        }
+
+        #[test]
+        fn it_renders_yaml() {
+            let (result, _) = export().render(ExportFormat::Yaml);
+            assert_eq!(result, "\"FOO\": \"foo\"\n\"BAR\": \"it's\\n$HOME\"\n");
+        }
+
+        #[test]
+        fn it_skips_multiline_docker_values() {
+            let (result, skipped) = export().render(ExportFormat::Docker);
+            assert_eq!(result, "FOO=foo\n");
+            assert_eq!(skipped, vec!["BAR", "g", "PATH"]);
+        }
+
+        #[test]
+        fn it_escapes_systemd_values() {
+            let export = Export {
+                vars: vec![("FOO", "a\\$b".to_string())],
+                ..Default::default()
+            };
+            let (result, _) = export.render(ExportFormat::Systemd);
+            assert_eq!(result, "FOO=a\\\\\\$b\n");
+        }
+
+        #[test]
+        fn it_renders_sh() {
+            let (result, skipped) = export().render(ExportFormat::Sh);
+            assert_eq!(
+                result,
+                "export FOO=foo\nexport BAR='it'\\''s\n$HOME'\nexport PATH=/a/bin\"${PATH:+:$PATH}\"\nalias g=git\n"
+            );
+            assert_eq!(skipped, Vec::<&str>::new());
+        }
+    }
+}
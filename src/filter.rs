@@ -14,6 +14,11 @@ pub struct FilterOptions<'a> {
 }
 
 impl<'a> FilterOptions<'a> {
+    /// Whether only a subset of the targets' values were asked for.
+    pub fn is_narrowed(&self) -> bool {
+        self.target.is_some() || !self.globs.is_empty() || !self.filters.is_empty()
+    }
+
     pub fn glob_match(&self, kinds: &[&str], value: &str) -> bool {
         check_matches(
             self.globs,
@@ -87,6 +92,7 @@ pub fn parse_match_option(value: Option<&str>) -> Vec<MatchOption<'_>> {
 }
 
 #[cfg(test)]
+#[allow(clippy::field_reassign_with_default)]
 mod tests {
     mod parse_match_options {
         use super::super::*;
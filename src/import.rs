@@ -0,0 +1,230 @@
+/// How values which already exist in the saucefile are handled by an import.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum OnConflict {
+    /// Nothing is imported if any value already exists.
+    Abort,
+    Overwrite,
+    Skip,
+}
+
+/// The values read from a `.env` file, or an `.envrc`-style shell script.
+#[derive(Debug, Default, PartialEq, Eq)]
+pub struct Imported {
+    pub vars: Vec<(String, String)>,
+    pub aliases: Vec<(String, String)>,
+    /// The (1-indexed) line number and content of each statement which isn't a
+    /// simple assignment, and was therefore skipped.
+    pub skipped: Vec<(usize, String)>,
+}
+
+enum Statement {
+    Var(String, String),
+    Alias(String, String),
+    Blank,
+    Unsupported,
+}
+
+impl Imported {
+    /// Parses `NAME=value` assignments (optionally prefixed by `export`) and
+    /// `alias name=value` lines.
+    ///
+    /// Values may be single quoted (taken literally), double quoted (supporting
+    /// escapes, i.e. `\n`) or unquoted, and quoted values may span multiple lines.
+    pub fn parse(content: &str) -> Self {
+        let mut result = Self::default();
+        let mut rest = content;
+        let mut line = 1;
+
+        while !rest.is_empty() {
+            let (consumed, statement) = parse_statement(rest);
+            match statement {
+                Statement::Var(name, value) => result.vars.push((name, value)),
+                Statement::Alias(name, value) => result.aliases.push((name, value)),
+                Statement::Blank => {}
+                Statement::Unsupported => {
+                    let text = rest[..consumed].trim_end().to_string();
+                    result.skipped.push((line, text));
+                }
+            }
+            line += rest[..consumed].matches('\n').count();
+            rest = &rest[consumed..];
+        }
+        result
+    }
+}
+
+/// Parses the statement at the start of `input`, returning the number of bytes it
+/// spans (including its trailing newline).
+fn parse_statement(input: &str) -> (usize, Statement) {
+    let line_end = input.find('\n').map(|i| i + 1).unwrap_or(input.len());
+    let line = input[..line_end].trim();
+    if line.is_empty() || line.starts_with('#') {
+        return (line_end, Statement::Blank);
+    }
+
+    let statement = input.trim_start_matches([' ', '\t']);
+    let (is_alias, assignment) = if let Some(rest) = statement.strip_prefix("export ") {
+        (false, rest.trim_start_matches([' ', '\t']))
+    } else if let Some(rest) = statement.strip_prefix("alias ") {
+        (true, rest.trim_start_matches([' ', '\t']))
+    } else {
+        (false, statement)
+    };
+
+    let (name, raw_value) = match assignment.split_once('=') {
+        Some((name, raw_value)) if is_valid_name(name, is_alias) => (name, raw_value),
+        _ => return (line_end, Statement::Unsupported),
+    };
+
+    let (value, used) = match parse_value(raw_value) {
+        Some(parsed) => parsed,
+        None => return (line_end, Statement::Unsupported),
+    };
+
+    // Anything but a comment following the value isn't a simple assignment.
+    let trailing = &raw_value[used..];
+    let trailing_end = trailing.find('\n').map(|i| i + 1).unwrap_or(trailing.len());
+    let remainder = trailing[..trailing_end].trim();
+    if !remainder.is_empty() && !remainder.starts_with('#') {
+        return (line_end, Statement::Unsupported);
+    }
+
+    let consumed = input.len() - trailing.len() + trailing_end;
+    let name = name.to_string();
+    if is_alias {
+        (consumed, Statement::Alias(name, value))
+    } else {
+        (consumed, Statement::Var(name, value))
+    }
+}
+
+fn is_valid_name(name: &str, is_alias: bool) -> bool {
+    let mut chars = name.chars();
+    let valid_first = match chars.next() {
+        Some(c) => c.is_ascii_alphabetic() || c == '_' || (is_alias && c.is_ascii_digit()),
+        None => false,
+    };
+    valid_first
+        && chars.all(|c| c.is_ascii_alphanumeric() || c == '_' || (is_alias && "-.".contains(c)))
+}
+
+/// Parses a (possibly quoted) value, returning it along with the number of bytes
+/// of `input` it spans, or `None` if a quote is left unterminated.
+fn parse_value(input: &str) -> Option<(String, usize)> {
+    let mut value = String::new();
+    let mut chars = input.char_indices().peekable();
+
+    while let Some(&(i, c)) = chars.peek() {
+        match c {
+            '\'' => {
+                chars.next();
+                loop {
+                    match chars.next()? {
+                        (_, '\'') => break,
+                        (_, c) => value.push(c),
+                    }
+                }
+            }
+            '"' => {
+                chars.next();
+                loop {
+                    match chars.next()? {
+                        (_, '"') => break,
+                        (_, '\\') => match chars.next()? {
+                            (_, 'n') => value.push('\n'),
+                            (_, '\n') => {}
+                            (_, c @ ('"' | '\\' | '$' | '`')) => value.push(c),
+                            (_, c) => {
+                                value.push('\\');
+                                value.push(c);
+                            }
+                        },
+                        (_, c) => value.push(c),
+                    }
+                }
+            }
+            '\\' => {
+                chars.next();
+                match chars.next() {
+                    Some((_, '\n')) | None => {}
+                    Some((_, c)) => value.push(c),
+                }
+            }
+            c if c.is_whitespace() => return Some((value, i)),
+            c => {
+                value.push(c);
+                chars.next();
+            }
+        }
+    }
+    Some((value, input.len()))
+}
+
+#[cfg(test)]
+mod tests {
+    mod parse {
+        use super::super::*;
+        use indoc::indoc;
+        use pretty_assertions::assert_eq;
+
+        fn pair(name: &str, value: &str) -> (String, String) {
+            (name.to_string(), value.to_string())
+        }
+
+        #[test]
+        fn it_parses_dotenv() {
+            let content = indoc!(
+                r#"
+                # comment
+                FOO=foo
+                export BAR='it''s' # trailing comment
+                BAZ="multi
+                line \"baz\"\n"
+                EMPTY=
+                "#
+            );
+            let result = Imported::parse(content);
+            assert_eq!(
+                result.vars,
+                vec![
+                    pair("FOO", "foo"),
+                    pair("BAR", "its"),
+                    pair("BAZ", "multi\nline \"baz\"\n"),
+                    pair("EMPTY", ""),
+                ]
+            );
+            assert_eq!(result.skipped, vec![]);
+        }
+
+        #[test]
+        fn it_parses_envrc() {
+            let content = indoc!(
+                r#"
+                source_up
+                export AWS_PROFILE=work
+                alias g='git status'
+                PATH_add bin
+                export FOO=bar && echo
+                "#
+            );
+            let result = Imported::parse(content);
+            assert_eq!(result.vars, vec![pair("AWS_PROFILE", "work")]);
+            assert_eq!(result.aliases, vec![pair("g", "git status")]);
+            assert_eq!(
+                result.skipped,
+                vec![
+                    (1, "source_up".to_string()),
+                    (4, "PATH_add bin".to_string()),
+                    (5, "export FOO=bar && echo".to_string()),
+                ]
+            );
+        }
+
+        #[test]
+        fn it_skips_unterminated_quotes() {
+            let result = Imported::parse("FOO='foo\nBAR=bar\n");
+            assert_eq!(result.vars, vec![pair("BAR", "bar")]);
+            assert_eq!(result.skipped, vec![(1, "FOO='foo".to_string())]);
+        }
+    }
+}
@@ -0,0 +1,731 @@
+use indexmap::{IndexMap, IndexSet};
+use std::{cell::RefCell, collections::HashMap, path::PathBuf};
+use subprocess::{Exec, ExitStatus, Redirection};
+
+use crate::{
+    colors::{RED, YELLOW},
+    output::{ErrorCode, Output},
+    value::TargetValue,
+};
+
+/// Resolves `${NAME}` references (and `{ cmd = ".." }` commands) inside saucefile
+/// values, and renders the `{{NAME}}` references of templates.
+///
+/// `variables` maps each environment variable name to every definition of it
+/// found in the cascade, ordered from the outermost saucefile to the innermost.
+///
+/// A reference to another name resolves to that name's final (innermost) value,
+/// falling back to the process environment. A reference to the name currently
+/// being defined resolves to the next definition further up the cascade (and
+/// then the process environment), such that `PATH = "${PATH}:bin"` extends,
+/// rather than recurses.
+///
+/// A value whose command fails (or which references such a value) resolves to
+/// `None`, such that it's left out rather than set to an empty string.
+#[derive(Debug, Default)]
+pub struct Interpolator<'a> {
+    variables: IndexMap<&'a str, Vec<TargetValue>>,
+    show: bool,
+    tag: String,
+    template_dir: PathBuf,
+    commands: Option<&'a CommandCache>,
+    resolved: RefCell<HashMap<Definition<'a>, Option<String>>>,
+}
+
+/// The output of each command run so far (or `None`, if it failed), keyed by the
+/// (expanded) command, such that each command only runs once per load.
+pub type CommandCache = RefCell<HashMap<String, Option<String>>>;
+
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub enum InterpolationError {
+    Unresolved {
+        owner: String,
+        /// The reference as written, i.e. `${NAME}`.
+        reference: String,
+    },
+    Cycle(Vec<String>),
+    CommandFailed {
+        owner: String,
+        command: String,
+        reason: String,
+    },
+    TemplateUnreadable {
+        owner: String,
+        template: PathBuf,
+        reason: String,
+    },
+}
+
+type Definition<'a> = (&'a str, usize);
+
+/// The result of looking up a reference.
+enum Resolution {
+    Value(String),
+    /// Undefined (or cyclic), such that the reference is left as written.
+    Unresolved,
+    /// Its value depends on a command which failed.
+    Failed,
+}
+
+impl<'a> Interpolator<'a> {
+    pub fn new(variables: IndexMap<&'a str, Vec<TargetValue>>) -> Self {
+        Self {
+            variables,
+            ..Default::default()
+        }
+    }
+
+    /// When set, commands are rendered rather than executed.
+    pub fn show(mut self, value: bool) -> Self {
+        self.show = value;
+        self
+    }
+
+    /// The tag (i.e. `--as`) rendered by a template's `{{tag}}`.
+    pub fn tag(mut self, tag: &str) -> Self {
+        self.tag = tag.to_string();
+        self
+    }
+
+    /// The directory against which `{ template-file = ".." }` paths are resolved.
+    pub fn template_dir(mut self, dir: PathBuf) -> Self {
+        self.template_dir = dir;
+        self
+    }
+
+    /// The cache in which command output is shared between interpolators.
+    pub fn commands(mut self, cache: &'a CommandCache) -> Self {
+        self.commands = Some(cache);
+        self
+    }
+
+    /// Resolves the final `value` of the environment variable `name`.
+    pub fn resolve_var(
+        &self,
+        name: &str,
+        value: &TargetValue,
+        errors: &mut IndexSet<InterpolationError>,
+    ) -> Option<String> {
+        let definition = self
+            .variables
+            .get_key_value(name)
+            .map(|(name, values)| (*name, values.len() - 1));
+
+        match definition {
+            Some(definition) => match self.resolve(definition, &mut Vec::new(), errors) {
+                Resolution::Value(value) => Some(value),
+                Resolution::Unresolved => Some(String::new()),
+                Resolution::Failed => None,
+            },
+            None => self.evaluate(name, None, value, &mut Vec::new(), errors),
+        }
+    }
+
+    /// Resolves the `value` of some non-variable target (alias, function, etc).
+    pub fn interpolate(
+        &self,
+        name: &str,
+        value: &TargetValue,
+        errors: &mut IndexSet<InterpolationError>,
+    ) -> Option<String> {
+        self.evaluate(name, None, value, &mut Vec::new(), errors)
+    }
+
+    /// Resolves the `value` of a hook. Literal values are shell code which runs
+    /// after the values are loaded, so their `${NAME}`s are left to the shell.
+    pub fn verbatim(
+        &self,
+        name: &str,
+        value: &TargetValue,
+        errors: &mut IndexSet<InterpolationError>,
+    ) -> Option<String> {
+        match value {
+            TargetValue::Literal(value) => Some(value.clone()),
+            value => self.interpolate(name, value, errors),
+        }
+    }
+
+    fn resolve(
+        &self,
+        definition: Definition<'a>,
+        stack: &mut Vec<Definition<'a>>,
+        errors: &mut IndexSet<InterpolationError>,
+    ) -> Resolution {
+        let (name, index) = definition;
+
+        if let Some(position) = stack.iter().position(|d| *d == definition) {
+            let mut names: Vec<String> = stack[position..]
+                .iter()
+                .map(|(n, _)| n.to_string())
+                .collect();
+            names.push(name.to_string());
+            errors.insert(InterpolationError::Cycle(names));
+            return Resolution::Unresolved;
+        }
+
+        if let Some(value) = self.resolved.borrow().get(&definition) {
+            return value.clone().map_or(Resolution::Failed, Resolution::Value);
+        }
+
+        let value = &self.variables[name][index];
+
+        stack.push(definition);
+        let result = self.evaluate(name, Some(definition), value, stack, errors);
+        stack.pop();
+
+        self.resolved
+            .borrow_mut()
+            .insert(definition, result.clone());
+        result.map_or(Resolution::Failed, Resolution::Value)
+    }
+
+    fn evaluate(
+        &self,
+        owner_name: &str,
+        owner: Option<Definition<'a>>,
+        value: &TargetValue,
+        stack: &mut Vec<Definition<'a>>,
+        errors: &mut IndexSet<InterpolationError>,
+    ) -> Option<String> {
+        match value {
+            TargetValue::Literal(value) => self.expand(owner_name, owner, value, stack, errors),
+            TargetValue::Command(command) => {
+                let command = self.expand(owner_name, owner, command, stack, errors)?;
+                if self.show {
+                    Some(TargetValue::Command(command).to_string())
+                } else {
+                    self.run(owner_name, &command, errors)
+                }
+            }
+            TargetValue::Template(template) => {
+                self.render(owner_name, owner, template, stack, errors)
+            }
+            TargetValue::TemplateFile(file) => {
+                let path = self.template_dir.join(file);
+                match std::fs::read_to_string(&path) {
+                    Ok(template) => self.render(owner_name, owner, &template, stack, errors),
+                    Err(error) => {
+                        errors.insert(InterpolationError::TemplateUnreadable {
+                            owner: owner_name.to_string(),
+                            template: path,
+                            reason: error.to_string(),
+                        });
+                        None
+                    }
+                }
+            }
+            TargetValue::Unset => Some(String::new()),
+        }
+    }
+
+    /// Runs `command`, unless it's already been run during this load.
+    fn run(
+        &self,
+        owner_name: &str,
+        command: &str,
+        errors: &mut IndexSet<InterpolationError>,
+    ) -> Option<String> {
+        let cache = match self.commands {
+            Some(cache) => cache,
+            None => return run_command(owner_name, command, errors),
+        };
+
+        if let Some(output) = cache.borrow().get(command) {
+            return output.clone();
+        }
+
+        let output = run_command(owner_name, command, errors);
+        cache
+            .borrow_mut()
+            .insert(command.to_string(), output.clone());
+        output
+    }
+
+    /// Looks up the value of `reference`, which was `written` (i.e. `${NAME}`) in
+    /// the value of `owner_name`.
+    #[allow(clippy::too_many_arguments)]
+    fn lookup(
+        &self,
+        owner_name: &str,
+        reference: &str,
+        written: &str,
+        owner: Option<Definition<'a>>,
+        stack: &mut Vec<Definition<'a>>,
+        errors: &mut IndexSet<InterpolationError>,
+    ) -> Resolution {
+        let definition = match owner {
+            Some((name, index)) if name == reference => {
+                index.checked_sub(1).map(|index| (name, index))
+            }
+            _ => self
+                .variables
+                .get_key_value(reference)
+                .map(|(name, values)| (*name, values.len() - 1)),
+        };
+
+        match definition {
+            // An unset variable is deliberately undefined, so it mustn't fall back to
+            // whatever value the environment (i.e. an ancestor saucefile) gave it.
+            Some((name, index)) if self.variables[name][index] == TargetValue::Unset => {
+                errors.insert(InterpolationError::Unresolved {
+                    owner: owner_name.to_string(),
+                    reference: written.to_string(),
+                });
+                Resolution::Unresolved
+            }
+            Some(definition) => self.resolve(definition, stack, errors),
+            None => match std::env::var(reference) {
+                Ok(value) => Resolution::Value(value),
+                Err(_) => {
+                    errors.insert(InterpolationError::Unresolved {
+                        owner: owner_name.to_string(),
+                        reference: written.to_string(),
+                    });
+                    Resolution::Unresolved
+                }
+            },
+        }
+    }
+
+    fn expand(
+        &self,
+        owner_name: &str,
+        owner: Option<Definition<'a>>,
+        value: &str,
+        stack: &mut Vec<Definition<'a>>,
+        errors: &mut IndexSet<InterpolationError>,
+    ) -> Option<String> {
+        let mut result = String::new();
+        let mut rest = value;
+
+        while let Some(start) = rest.find('$') {
+            result.push_str(&rest[..start]);
+            let tail = &rest[start..];
+
+            if let Some(escaped) = tail.strip_prefix("$${") {
+                result.push_str("${");
+                rest = escaped;
+                continue;
+            }
+
+            match parse_reference(tail) {
+                Some((reference, remainder)) => {
+                    let written = &tail[..tail.len() - remainder.len()];
+                    match self.lookup(owner_name, reference, written, owner, stack, errors) {
+                        Resolution::Value(resolved) => result.push_str(&resolved),
+                        Resolution::Unresolved => result.push_str(written),
+                        Resolution::Failed => return None,
+                    }
+                    rest = remainder;
+                }
+                None => {
+                    result.push('$');
+                    rest = &tail[1..];
+                }
+            }
+        }
+        result.push_str(rest);
+        Some(result)
+    }
+
+    /// Renders the `{{NAME}}` references in `template`, where `{{tag}}` is the tag
+    /// being loaded (and `{{env.NAME}}` always refers to an env var, even one named
+    /// `tag`). Unlike `${NAME}`, which templates leave alone (as it's often
+    /// meaningful to whatever reads the file), references are only resolved against
+    /// the env vars. A literal `{{` is written as `\{{`.
+    fn render(
+        &self,
+        owner_name: &str,
+        owner: Option<Definition<'a>>,
+        template: &str,
+        stack: &mut Vec<Definition<'a>>,
+        errors: &mut IndexSet<InterpolationError>,
+    ) -> Option<String> {
+        let mut result = String::new();
+        let mut rest = template;
+
+        while let Some(start) = rest.find("{{") {
+            if rest[..start].ends_with('\\') {
+                result.push_str(&rest[..start - 1]);
+                result.push_str("{{");
+                rest = &rest[start + 2..];
+                continue;
+            }
+
+            result.push_str(&rest[..start]);
+            let tail = &rest[start..];
+            let end = match tail.find("}}") {
+                Some(end) => end + 2,
+                None => {
+                    rest = tail;
+                    break;
+                }
+            };
+
+            let written = &tail[..end];
+            let name = written[2..end - 2].trim();
+            let value = if name == "tag" {
+                Resolution::Value(self.tag.clone())
+            } else {
+                let name = name.strip_prefix("env.").unwrap_or(name);
+                if is_name(name) {
+                    self.lookup(owner_name, name, written, owner, stack, errors)
+                } else {
+                    Resolution::Unresolved
+                }
+            };
+            match value {
+                Resolution::Value(value) => result.push_str(&value),
+                Resolution::Unresolved => result.push_str(written),
+                Resolution::Failed => return None,
+            }
+            rest = &tail[end..];
+        }
+        result.push_str(rest);
+        Some(result)
+    }
+}
+
+/// Executes `command`, yielding its stdout (less trailing newlines), or `None` if
+/// it fails.
+fn run_command(
+    owner: &str,
+    command: &str,
+    errors: &mut IndexSet<InterpolationError>,
+) -> Option<String> {
+    let result = Exec::shell(command).stdout(Redirection::Pipe).capture();
+
+    let reason = match result {
+        Ok(data) if data.success() => {
+            return Some(data.stdout_str().trim_end_matches('\n').to_string());
+        }
+        Ok(data) => match data.exit_status {
+            ExitStatus::Exited(code) => format!("exited with code {}", code),
+            ExitStatus::Signaled(signal) => format!("killed by signal {}", signal),
+            _ => "exited abnormally".to_string(),
+        },
+        Err(error) => error.to_string(),
+    };
+
+    errors.insert(InterpolationError::CommandFailed {
+        owner: owner.to_string(),
+        command: command.to_string(),
+        reason,
+    });
+    None
+}
+
+/// Parses a leading `${NAME}`, returning the name and the remaining text.
+fn parse_reference(value: &str) -> Option<(&str, &str)> {
+    let inner = value.strip_prefix("${")?;
+    let end = inner.find('}')?;
+    let name = &inner[..end];
+
+    if is_name(name) {
+        Some((name, &inner[end + 1..]))
+    } else {
+        None
+    }
+}
+
+/// Whether `name` is a valid environment variable name.
+fn is_name(name: &str) -> bool {
+    let mut chars = name.chars();
+    matches!(chars.next(), Some(c) if c.is_ascii_alphabetic() || c == '_')
+        && chars.all(|c| c.is_ascii_alphanumeric() || c == '_')
+}
+
+pub fn report_errors(errors: IndexSet<InterpolationError>, output: &mut Output) {
+    for error in errors {
+        let code = match &error {
+            InterpolationError::CommandFailed { .. } => ErrorCode::CommandError,
+            InterpolationError::TemplateUnreadable { .. } => ErrorCode::ParseError,
+            _ => ErrorCode::InterpolationError,
+        };
+        let message = match &error {
+            InterpolationError::Unresolved { owner, reference } => vec![
+                RED.bold().paint("Unresolved reference "),
+                YELLOW.bold().paint(reference.to_string()),
+                RED.bold().paint(" in "),
+                YELLOW.bold().paint(owner.to_string()),
+            ],
+            InterpolationError::Cycle(names) => vec![
+                RED.bold().paint("Cyclic reference "),
+                YELLOW.bold().paint(names.join(" -> ")),
+            ],
+            InterpolationError::CommandFailed {
+                owner,
+                command,
+                reason,
+            } => vec![
+                RED.bold().paint("Command "),
+                YELLOW.bold().paint(command.to_string()),
+                RED.bold().paint(" for "),
+                YELLOW.bold().paint(owner.to_string()),
+                RED.bold().paint(format!(" failed: {}", reason)),
+            ],
+            InterpolationError::TemplateUnreadable {
+                owner,
+                template,
+                reason,
+            } => vec![
+                RED.bold().paint("Couldn't read template "),
+                YELLOW.bold().paint(template.to_string_lossy().to_string()),
+                RED.bold().paint(" for "),
+                YELLOW.bold().paint(owner.to_string()),
+                RED.bold().paint(format!(": {}", reason)),
+            ],
+        };
+        output.notify_error(code, &message);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    mod interpolate {
+        use super::super::*;
+        use pretty_assertions::assert_eq;
+
+        fn interpolator(values: &[(&'static str, &[&str])]) -> Interpolator<'static> {
+            Interpolator::new(
+                values
+                    .iter()
+                    .map(|(k, v)| (*k, v.iter().map(|s| TargetValue::from(*s)).collect()))
+                    .collect(),
+            )
+        }
+
+        #[test]
+        fn it_leaves_plain_values_unchanged() {
+            let mut errors = IndexSet::new();
+            let result = interpolator(&[]).interpolate("foo", &"bar $1 ${1} $".into(), &mut errors);
+            assert_eq!(result.as_deref(), Some("bar $1 ${1} $"));
+            assert_eq!(errors.len(), 0);
+        }
+
+        #[test]
+        fn it_resolves_other_values() {
+            let mut errors = IndexSet::new();
+            let interpolator = interpolator(&[
+                ("HOST", &["localhost"]),
+                ("PORT", &["1", "5432"]),
+                ("URL", &["pg://${HOST}:${PORT}/app"]),
+            ]);
+            let result =
+                interpolator.resolve_var("URL", &"pg://${HOST}:${PORT}/app".into(), &mut errors);
+            assert_eq!(result.as_deref(), Some("pg://localhost:5432/app"));
+            assert_eq!(errors.len(), 0);
+        }
+
+        #[test]
+        fn it_resolves_self_references_up_the_cascade() {
+            std::env::set_var("SAUCE_TEST_INTERPOLATE_SELF", "env");
+
+            let mut errors = IndexSet::new();
+            let interpolator = interpolator(&[(
+                "SAUCE_TEST_INTERPOLATE_SELF",
+                &[
+                    "${SAUCE_TEST_INTERPOLATE_SELF}:a",
+                    "${SAUCE_TEST_INTERPOLATE_SELF}:b",
+                ],
+            )]);
+            let result = interpolator.resolve_var(
+                "SAUCE_TEST_INTERPOLATE_SELF",
+                &"${SAUCE_TEST_INTERPOLATE_SELF}:b".into(),
+                &mut errors,
+            );
+            assert_eq!(result.as_deref(), Some("env:a:b"));
+            assert_eq!(errors.len(), 0);
+        }
+
+        #[test]
+        fn it_falls_back_to_the_environment() {
+            std::env::set_var("SAUCE_TEST_INTERPOLATE_ENV", "meow");
+
+            let mut errors = IndexSet::new();
+            let result = interpolator(&[]).interpolate(
+                "foo",
+                &"${SAUCE_TEST_INTERPOLATE_ENV}!".into(),
+                &mut errors,
+            );
+            assert_eq!(result.as_deref(), Some("meow!"));
+        }
+
+        #[test]
+        fn it_escapes_references() {
+            let mut errors = IndexSet::new();
+            let result =
+                interpolator(&[("FOO", &["1"])]).interpolate("bar", &"$${FOO}".into(), &mut errors);
+            assert_eq!(result.as_deref(), Some("${FOO}"));
+            assert_eq!(errors.len(), 0);
+        }
+
+        #[test]
+        fn it_reports_unresolved_references() {
+            let mut errors = IndexSet::new();
+            let result = interpolator(&[]).interpolate(
+                "foo",
+                &"a${SAUCE_TEST_DOES_NOT_EXIST}b".into(),
+                &mut errors,
+            );
+            assert_eq!(result.as_deref(), Some("a${SAUCE_TEST_DOES_NOT_EXIST}b"));
+            assert_eq!(
+                errors.into_iter().collect::<Vec<_>>(),
+                vec![InterpolationError::Unresolved {
+                    owner: "foo".to_string(),
+                    reference: "${SAUCE_TEST_DOES_NOT_EXIST}".to_string()
+                }]
+            );
+        }
+
+        #[test]
+        fn it_detects_cycles() {
+            let mut errors = IndexSet::new();
+            let interpolator = interpolator(&[("A", &["${B}"]), ("B", &["${A}"])]);
+            let result = interpolator.resolve_var("A", &"${B}".into(), &mut errors);
+            assert_eq!(result.as_deref(), Some("${A}"));
+            assert_eq!(
+                errors.into_iter().collect::<Vec<_>>(),
+                vec![InterpolationError::Cycle(vec![
+                    "A".to_string(),
+                    "B".to_string(),
+                    "A".to_string()
+                ])]
+            );
+        }
+
+        #[test]
+        fn it_runs_commands() {
+            let mut errors = IndexSet::new();
+            let interpolator = interpolator(&[("NAME", &["meow"])]);
+            let result = interpolator.interpolate(
+                "foo",
+                &TargetValue::Command("echo ${NAME}".to_string()),
+                &mut errors,
+            );
+            assert_eq!(result.as_deref(), Some("meow"));
+            assert_eq!(errors.len(), 0);
+        }
+
+        #[test]
+        fn it_shows_commands_rather_than_running_them() {
+            let mut errors = IndexSet::new();
+            let interpolator = interpolator(&[]).show(true);
+            let result = interpolator.interpolate(
+                "foo",
+                &TargetValue::Command("exit 1".to_string()),
+                &mut errors,
+            );
+            assert_eq!(result.as_deref(), Some("$(exit 1)"));
+            assert_eq!(errors.len(), 0);
+        }
+
+        #[test]
+        fn it_reports_failed_commands() {
+            let mut errors = IndexSet::new();
+            let result = interpolator(&[]).interpolate(
+                "foo",
+                &TargetValue::Command("exit 3".to_string()),
+                &mut errors,
+            );
+            assert_eq!(result, None);
+            assert_eq!(
+                errors.into_iter().collect::<Vec<_>>(),
+                vec![InterpolationError::CommandFailed {
+                    owner: "foo".to_string(),
+                    command: "exit 3".to_string(),
+                    reason: "exited with code 3".to_string(),
+                }]
+            );
+        }
+
+        #[test]
+        fn it_fails_values_referencing_failed_commands() {
+            let mut errors = IndexSet::new();
+            let interpolator = Interpolator::new(IndexMap::from([
+                ("TOKEN", vec![TargetValue::Command("exit 1".to_string())]),
+                ("URL", vec!["pg://${TOKEN}@localhost".into()]),
+            ]));
+
+            let result =
+                interpolator.resolve_var("URL", &"pg://${TOKEN}@localhost".into(), &mut errors);
+            assert_eq!(result, None);
+            assert_eq!(errors.len(), 1);
+        }
+
+        #[test]
+        fn it_runs_each_command_once_per_cache() {
+            let log = std::env::temp_dir().join(format!("sauce-test-{}", std::process::id()));
+            let _ = std::fs::remove_file(&log);
+            let command = TargetValue::Command(format!(
+                "echo run >> {0}; wc -l < {0}",
+                log.to_string_lossy()
+            ));
+
+            let cache = CommandCache::default();
+            let mut errors = IndexSet::new();
+            let results: Vec<_> = ["foo", "bar"]
+                .iter()
+                .map(|name| {
+                    Interpolator::new(IndexMap::new())
+                        .commands(&cache)
+                        .interpolate(name, &command, &mut errors)
+                })
+                .collect();
+            let _ = std::fs::remove_file(&log);
+
+            assert_eq!(results, vec![Some("1".to_string()), Some("1".to_string())]);
+            assert_eq!(errors.len(), 0);
+        }
+
+        #[test]
+        fn it_renders_templates() {
+            let mut errors = IndexSet::new();
+            let interpolator =
+                interpolator(&[("HOST", &["localhost"]), ("URL", &["pg://${HOST}"])]).tag("prod");
+            let result = interpolator.interpolate(
+                ".env",
+                &TargetValue::Template("DB={{URL}}\n{{ tag }} ${HOST} {{not a name}}".to_string()),
+                &mut errors,
+            );
+            assert_eq!(
+                result.as_deref(),
+                Some("DB=pg://localhost\nprod ${HOST} {{not a name}}")
+            );
+            assert_eq!(errors.len(), 0);
+        }
+
+        #[test]
+        fn it_escapes_template_references() {
+            let mut errors = IndexSet::new();
+            let interpolator = interpolator(&[("tag", &["mine"])]).tag("prod");
+            let result = interpolator.interpolate(
+                ".env",
+                &TargetValue::Template("\\{{tag}} {{tag}} {{env.tag}}".to_string()),
+                &mut errors,
+            );
+            assert_eq!(result.as_deref(), Some("{{tag}} prod mine"));
+            assert_eq!(errors.len(), 0);
+        }
+
+        #[test]
+        fn it_reports_unreadable_template_files() {
+            let mut errors = IndexSet::new();
+            let interpolator = interpolator(&[]).template_dir(PathBuf::from("/nonexistent"));
+            let result = interpolator.interpolate(
+                ".env",
+                &TargetValue::TemplateFile("env.tmpl".to_string()),
+                &mut errors,
+            );
+            assert_eq!(result, None);
+            assert_eq!(
+                matches!(
+                    errors.first(),
+                    Some(InterpolationError::TemplateUnreadable { template, .. })
+                        if template == &PathBuf::from("/nonexistent/env.tmpl")
+                ),
+                true
+            );
+        }
+    }
+}
@@ -0,0 +1,111 @@
+use toml_edit::Item;
+
+use crate::value::TargetValue;
+
+/// A built-in `[layout]`, which expands to the env var and `PATH` entry that go
+/// along with a toolchain's project-local install directory.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Layout {
+    Python,
+    Node,
+    Go,
+    Ruby,
+    Rust,
+}
+
+impl Layout {
+    pub const ALL: [Self; 5] = [Self::Python, Self::Node, Self::Go, Self::Ruby, Self::Rust];
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            Self::Python => "python",
+            Self::Node => "node",
+            Self::Go => "go",
+            Self::Ruby => "ruby",
+            Self::Rust => "rust",
+        }
+    }
+
+    /// The directory used by `<layout> = true`, relative to the saucefile's directory.
+    fn default_dir(&self) -> &'static str {
+        match self {
+            Self::Python => ".venv",
+            Self::Node => "node_modules",
+            Self::Go => ".",
+            Self::Ruby => ".gem",
+            Self::Rust => ".cargo",
+        }
+    }
+
+    /// The env var which is set to the layout's directory, if any.
+    pub fn var(&self) -> Option<&'static str> {
+        match self {
+            Self::Python => Some("VIRTUAL_ENV"),
+            Self::Node => None,
+            Self::Go => Some("GOPATH"),
+            Self::Ruby => Some("GEM_HOME"),
+            Self::Rust => Some("CARGO_INSTALL_ROOT"),
+        }
+    }
+
+    /// The directory (within the layout's directory) which is prepended to `PATH`.
+    pub fn bin(&self) -> &'static str {
+        match self {
+            Self::Node => ".bin",
+            _ => "bin",
+        }
+    }
+
+    /// Reads the `[layout]` section `item`, returning each enabled layout along with
+    /// its (as yet unresolved) directory.
+    ///
+    /// A layout is enabled by `true` (using its default directory) or by the path to
+    /// its directory, optionally per tag.
+    pub fn from_item<T: AsRef<str>>(item: &Item, tags: &[T]) -> Vec<(Self, String)> {
+        Self::ALL
+            .into_iter()
+            .filter_map(
+                |layout| match TargetValue::select(&item[layout.name()], tags)? {
+                    TargetValue::Literal(value) if value == "true" => {
+                        Some((layout, layout.default_dir().to_string()))
+                    }
+                    TargetValue::Literal(value) if value == "false" => None,
+                    TargetValue::Literal(dir) => Some((layout, dir)),
+                    _ => None,
+                },
+            )
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    mod from_item {
+        use super::super::*;
+        use pretty_assertions::assert_eq;
+        use toml_edit::Document;
+
+        #[test]
+        fn it_reads_enabled_layouts() {
+            let document = r#"
+            [layout]
+            python = "env"
+            node = true
+            go = false
+            ruby = { default = true, ci = "/gems" }
+            "#
+            .parse::<Document>()
+            .unwrap();
+
+            let layouts = Layout::from_item(&document["layout"], &["ci", "default"]);
+            assert_eq!(
+                layouts,
+                vec![
+                    (Layout::Python, "env".to_string()),
+                    (Layout::Node, "node_modules".to_string()),
+                    (Layout::Ruby, "/gems".to_string()),
+                ]
+            );
+        }
+    }
+}
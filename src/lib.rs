@@ -1,12 +1,18 @@
 pub mod cli;
 mod colors;
+pub mod export;
 pub mod filter;
+pub mod import;
+mod interpolation;
+pub mod layout;
 pub mod output;
 pub mod saucefile;
 pub mod settings;
 pub mod shell;
+pub mod state;
 pub mod target;
 mod toml;
+pub mod value;
 
 pub mod test_utils;
 
@@ -1,4 +1,4 @@
-use crate::toml::{ensure_section, write_document};
+use crate::toml::{ensure_section, remove_value, set_value, write_document};
 use crate::{
     colors::{BLUE, RED, TABLE_BLUE, TABLE_YELLOW, YELLOW},
     toml::unwrap_toml_value,
@@ -18,6 +18,8 @@ pub struct Output {
     verbose: bool,
     show: bool,
     code: Option<ErrorCode>,
+    /// The exit status of a command run on the user's behalf, i.e. `sauce exec`.
+    exit_code: Option<i32>,
 }
 
 impl std::fmt::Debug for Output {
@@ -42,6 +44,7 @@ impl Output {
             verbose: false,
             show: false,
             code: None,
+            exit_code: None,
         }
     }
 
@@ -77,6 +80,10 @@ impl Output {
         self.show = value;
     }
 
+    pub fn is_show(&self) -> bool {
+        self.show
+    }
+
     fn format(&self, output: impl Display) -> String {
         let mut result = format!("{}", output);
         if !result.is_empty() {
@@ -170,8 +177,15 @@ impl Output {
         self.notify(message)
     }
 
+    /// Exits with the status of a command run on the user's behalf, rather than any
+    /// error encountered while preparing it.
+    pub fn set_exit_code(&mut self, code: i32) {
+        self.exit_code = Some(code);
+    }
+
     pub fn error_code(&self) -> Option<i32> {
-        self.code.clone().map(|c| c as i32)
+        self.exit_code
+            .or_else(|| self.code.clone().map(|c| c as i32))
     }
 
     pub fn flush(&mut self) -> Result<()> {
@@ -180,27 +194,35 @@ impl Output {
         Ok(())
     }
 
+    /// Writes `values` into the `heading` section, or only their entries for `tags`
+    /// when given.
     pub fn write_toml<I, T>(
         &mut self,
         file: &Path,
         document: &mut Document,
         heading: &str,
         values: I,
+        tags: Option<&[String]>,
     ) where
         I: IntoIterator<Item = (T, Item)>,
         T: AsRef<str>,
     {
         for (name, value) in values.into_iter() {
+            let value = value.as_value().unwrap();
+            let tags_label = tags.map(|tags| format!(" ({})", tags.join(", ")));
             self.notify(&[
                 "Setting ".into(),
                 BLUE.bold().paint(name.as_ref()),
+                YELLOW.paint(tags_label.unwrap_or_default()),
                 " = ".into(),
-                YELLOW.paint(unwrap_toml_value(value.as_value().unwrap())),
+                YELLOW.paint(unwrap_toml_value(value)),
             ]);
 
             if !self.show {
                 let section = ensure_section(document, heading);
-                section[name.as_ref()] = value;
+                if let Some(section) = section.as_table_mut() {
+                    set_value(section, name.as_ref(), value.clone(), tags);
+                }
             }
         }
 
@@ -209,6 +231,52 @@ impl Output {
         }
     }
 
+    /// Removes the keys `names` from whichever of the `headings` define them, or only
+    /// the entries for `tags` when given.
+    pub fn remove_toml<T: AsRef<str>>(
+        &mut self,
+        file: &Path,
+        document: &mut Document,
+        headings: &[&str],
+        names: &[T],
+        tags: Option<&[String]>,
+    ) {
+        let mut updated = document.clone();
+        let mut changed = false;
+
+        for name in names.iter() {
+            let name = name.as_ref();
+            let mut removed = false;
+            for heading in headings.iter() {
+                let section = updated.as_table_mut().get_mut(heading);
+                if let Some(section) = section.and_then(|s| s.as_table_mut()) {
+                    removed |= remove_value(section, name, tags);
+                }
+            }
+
+            let tags = tags.map(|tags| format!(" ({})", tags.join(", ")));
+            if removed {
+                changed = true;
+                self.notify(&[
+                    "Removing ".into(),
+                    BLUE.bold().paint(name),
+                    YELLOW.paint(tags.unwrap_or_default()),
+                ]);
+            } else {
+                self.notify(&[
+                    RED.bold().paint("No value found for "),
+                    YELLOW.bold().paint(name),
+                    YELLOW.paint(tags.unwrap_or_default()),
+                ]);
+            }
+        }
+
+        if changed && !self.show {
+            *document = updated;
+            write_document(file, document, self);
+        }
+    }
+
     pub fn create_file(&mut self, file: &Path) -> Result<(), String> {
         if let Some(parent) = file.parent() {
             let created = if self.show {
@@ -261,6 +329,97 @@ impl Output {
         Ok(())
     }
 
+    /// Writes `content` to `file`, or prints it (rather than writing it) in show mode.
+    pub fn write_file(&mut self, file: &Path, content: &str) -> Result<(), String> {
+        if self.show {
+            self.notify_str(content);
+            return Ok(());
+        }
+
+        if std::fs::write(file, content).is_err() {
+            return Err(self.notify_error(
+                ErrorCode::WriteError,
+                &[
+                    RED.bold().paint("Couldn't write "),
+                    YELLOW.paint(file.to_string_lossy()),
+                ],
+            ));
+        }
+
+        self.notify(&[
+            BLUE.bold().paint("Wrote "),
+            YELLOW.paint(file.to_string_lossy()),
+        ]);
+        Ok(())
+    }
+
+    /// Writes a `[file]` target, creating any missing parent directories, and applying
+    /// the permission bits `mode` (if given). In show mode, the write is described
+    /// rather than performed.
+    pub fn write_target_file(
+        &mut self,
+        file: &Path,
+        content: &str,
+        mode: Option<u32>,
+    ) -> Result<(), String> {
+        if self.show {
+            self.notify(&[
+                BLUE.bold().paint("Would write "),
+                YELLOW.paint(file.to_string_lossy()),
+            ]);
+            self.notify_str(content);
+            return Ok(());
+        }
+
+        let written = file
+            .parent()
+            .map_or(Ok(()), std::fs::create_dir_all)
+            .and_then(|_| write_with_mode(file, content, mode));
+
+        if written.is_err() {
+            return Err(self.notify_error(
+                ErrorCode::WriteError,
+                &[
+                    RED.bold().paint("Couldn't write "),
+                    YELLOW.paint(file.to_string_lossy()),
+                ],
+            ));
+        }
+
+        self.notify(&[
+            BLUE.bold().paint("Wrote "),
+            YELLOW.paint(file.to_string_lossy()),
+        ]);
+        Ok(())
+    }
+
+    /// Removes `file`, or describes (rather than performs) the removal in show mode.
+    pub fn remove_file(&mut self, file: &Path) -> Result<(), String> {
+        if self.show {
+            self.notify(&[
+                BLUE.bold().paint("Would remove "),
+                YELLOW.paint(file.to_string_lossy()),
+            ]);
+            return Ok(());
+        }
+
+        if std::fs::remove_file(file).is_err() {
+            return Err(self.notify_error(
+                ErrorCode::WriteError,
+                &[
+                    RED.bold().paint("Couldn't remove "),
+                    YELLOW.paint(file.to_string_lossy()),
+                ],
+            ));
+        }
+
+        self.notify(&[
+            BLUE.bold().paint("Removed "),
+            YELLOW.paint(file.to_string_lossy()),
+        ]);
+        Ok(())
+    }
+
     pub fn move_file(&mut self, source: &Path, dest: &Path, copy: bool) -> Result<(), String> {
         let moved = if self.show {
             true
@@ -298,4 +457,28 @@ impl Output {
 pub enum ErrorCode {
     WriteError = 1,
     ParseError = 2,
+    InterpolationError = 3,
+    CommandError = 4,
+}
+
+/// Writes `content` to `file`, which (on unix) is created with, and set to, the
+/// permission bits `mode`.
+fn write_with_mode(file: &Path, content: &str, mode: Option<u32>) -> std::io::Result<()> {
+    let mut options = std::fs::OpenOptions::new();
+    options.write(true).create(true).truncate(true);
+
+    #[cfg(unix)]
+    if let Some(mode) = mode {
+        use std::os::unix::fs::{OpenOptionsExt, PermissionsExt};
+
+        options.mode(mode);
+        let mut handle = options.open(file)?;
+        handle.write_all(content.as_bytes())?;
+        return std::fs::set_permissions(file, std::fs::Permissions::from_mode(mode));
+    }
+
+    #[cfg(not(unix))]
+    let _ = mode;
+
+    options.open(file)?.write_all(content.as_bytes())
 }
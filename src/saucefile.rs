@@ -1,18 +1,33 @@
-use crate::{filter::FilterOptions, output::Output};
-use crate::{settings::Settings, toml::unwrap_toml_value};
-use indexmap::IndexMap;
-use itertools::iproduct;
+use crate::colors::{RED, YELLOW};
+use crate::interpolation::{report_errors, CommandCache, Interpolator};
+use crate::output::ErrorCode;
+use crate::{filter::FilterOptions, layout::Layout, output::Output};
+use crate::{
+    settings::{RealizedSettings, Settings},
+    target::Target,
+    value::{unset_keys_tagged, unset_variants, Origin, PathEntries, PathPosition, TargetValue},
+};
+use indexmap::{IndexMap, IndexSet};
+use itertools::Itertools;
+use path_absolutize::Absolutize;
 use std::iter::once;
 
 use crate::toml::get_document;
-use std::path::PathBuf;
-use toml_edit::{Document, Item, Value};
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+use toml_edit::Document;
 
 #[derive(Debug)]
 pub struct Saucefile {
     pub path: Option<PathBuf>,
     pub ancestors: Vec<(PathBuf, Document)>,
     pub document: Document,
+    /// The directory targeted by each saucefile, keyed by the saucefile's path.
+    pub directories: HashMap<PathBuf, PathBuf>,
+    /// The directory against which `{ template-file = ".." }` paths are resolved.
+    pub config_dir: PathBuf,
+    /// The output of the commands run while loading, shared between sections.
+    pub commands: CommandCache,
 }
 
 impl Saucefile {
@@ -31,6 +46,17 @@ impl Saucefile {
 
             let document = get_document(&path, output);
 
+            // A saucefile which stops the cascade excludes every saucefile above it. Only
+            // `cascade` (or `root`) is read here, as the settings are otherwise
+            // interpreted (and reported when invalid) once the cascade is resolved.
+            let settings = &document["settings"];
+            let cascade = settings["cascade"]
+                .as_bool()
+                .or_else(|| settings["root"].as_bool().map(|root| !root));
+            if cascade == Some(false) {
+                base_sf.ancestors.clear();
+            }
+
             if paths.peek().is_some() {
                 base_sf.ancestors.push((path, document));
             } else {
@@ -41,6 +67,106 @@ impl Saucefile {
         base_sf
     }
 
+    /// Includes the profiles named by each saucefile's `use` setting, which are read
+    /// from the `profiles` library under the config dir.
+    ///
+    /// A profile's values apply ahead of those of the saucefile which uses it, and
+    /// its relative paths are resolved against that saucefile's directory. Profiles
+    /// may themselves use other profiles, but each is only included once, where it's
+    /// first used: an inner saucefile using it again doesn't re-apply it over the
+    /// values of the saucefiles in between.
+    pub fn use_profiles(&mut self, output: &mut Output) {
+        let mut result = Vec::new();
+        for (path, document) in std::mem::take(&mut self.ancestors) {
+            self.include_profiles(&path, &document, &mut Vec::new(), &mut result, output);
+            result.push((path, document));
+        }
+
+        if let Some(path) = self.path.clone() {
+            let document = std::mem::take(&mut self.document);
+            self.include_profiles(&path, &document, &mut Vec::new(), &mut result, output);
+            self.document = document;
+        }
+        self.ancestors = result;
+    }
+
+    fn include_profiles(
+        &mut self,
+        user: &Path,
+        document: &Document,
+        stack: &mut Vec<String>,
+        result: &mut Vec<(PathBuf, Document)>,
+        output: &mut Output,
+    ) {
+        // Only `use` is read here, as the settings are otherwise interpreted (and
+        // reported when invalid) once the cascade is resolved.
+        let names = document["settings"]["use"]
+            .as_array()
+            .map(|names| {
+                names
+                    .iter()
+                    .filter_map(|name| name.as_str().map(|name| name.to_string()))
+                    .collect::<Vec<_>>()
+            })
+            .unwrap_or_default();
+
+        for name in names {
+            if stack.contains(&name) {
+                let cycle = stack.iter().chain(once(&name)).join(" -> ");
+                output.notify_error(
+                    ErrorCode::ParseError,
+                    &[
+                        RED.bold().paint("Profile cycle "),
+                        YELLOW.bold().paint(cycle),
+                    ],
+                );
+                continue;
+            }
+
+            // A profile is named, rather than given as a path, so it can't be read from
+            // outside of the `profiles` library.
+            if name.is_empty() || name.contains(['/', '\\']) {
+                output.notify_error(
+                    ErrorCode::ParseError,
+                    &[
+                        RED.bold().paint("Invalid profile name "),
+                        YELLOW.bold().paint(&name),
+                    ],
+                );
+                continue;
+            }
+
+            let path = self
+                .config_dir
+                .join("profiles")
+                .join(format!("{}.toml", name));
+            if result.iter().any(|(p, _)| *p == path) {
+                continue;
+            }
+            if !path.is_file() {
+                output.notify_error(
+                    ErrorCode::ParseError,
+                    &[
+                        RED.bold().paint("No profile named "),
+                        YELLOW.bold().paint(&name),
+                        RED.bold().paint(" (looked for "),
+                        YELLOW.bold().paint(path.to_string_lossy()),
+                        RED.bold().paint(")"),
+                    ],
+                );
+                continue;
+            }
+
+            let profile = get_document(&path, output);
+            stack.push(name);
+            self.include_profiles(user, &profile, stack, result, output);
+            stack.pop();
+
+            self.directories.insert(path.clone(), self.directory(user));
+            result.push((path, profile));
+        }
+    }
+
     pub fn settings(&self) -> Settings {
         if let Some(path) = &self.path {
             Settings::from_document(path.clone(), &self.document)
@@ -49,6 +175,27 @@ impl Saucefile {
         }
     }
 
+    /// The settings of every saucefile in the cascade, from the outermost saucefile
+    /// to the innermost, such that each takes precedence over those before it.
+    ///
+    /// `autoload-hook` and `autoload-args` are left out, as they're only read from the
+    /// global config (when the shell is initialized).
+    pub fn cascade_settings(&self) -> Vec<Settings> {
+        self.ancestors()
+            .map(|(path, document)| Settings {
+                autoload_hook: None,
+                autoload_args: None,
+                ..Settings::from_document(path.clone(), document)
+            })
+            .collect()
+    }
+
+    /// Resolves the settings inherited through the cascade, falling back to the
+    /// `global` settings.
+    pub fn resolve_settings(&self, global: &Settings) -> RealizedSettings {
+        Settings::resolve(once(global).chain(&self.cascade_settings()))
+    }
+
     fn ancestors(&self) -> impl Iterator<Item = (&PathBuf, &Document)> {
         let ancestors = self.ancestors.iter().map(|(p, d)| (p, d));
 
@@ -59,78 +206,393 @@ impl Saucefile {
         ancestors.chain(tail)
     }
 
+    /// The paths of the saucefiles which were read, from outermost to innermost.
     pub fn paths(&self) -> impl Iterator<Item = &PathBuf> {
-        self.ancestors().map(|(p, _)| p)
+        // An empty `path` is a placeholder for an innermost saucefile which doesn't exist.
+        self.ancestors()
+            .map(|(p, _)| p)
+            .filter(|p| !p.as_os_str().is_empty())
     }
 
-    fn documents(&self) -> impl Iterator<Item = &Document> {
-        self.ancestors().map(|(_, d)| d)
+    /// The directory targeted by the saucefile at `path`, against which its relative
+    /// paths are resolved.
+    pub fn directory(&self, path: &Path) -> PathBuf {
+        self.directories.get(path).cloned().unwrap_or_default()
+    }
+
+    /// Collects every definition of each key in the given `sections`, ordered from
+    /// the outermost saucefile in the cascade to the innermost.
+    fn layers(
+        &self,
+        sections: &[&str],
+        filter_options: &FilterOptions,
+    ) -> IndexMap<&str, Vec<TargetValue>> {
+        self.definitions(sections, filter_options)
+            .into_iter()
+            .map(|(key, origins)| (key, origins.into_iter().map(|o| o.value).collect()))
+            .collect()
     }
 
-    fn section(&self, sections: &[&str], filter_options: &FilterOptions) -> Vec<(&str, String)> {
+    /// Collects every definition per [`Saucefile::layers`], along with the saucefile
+    /// and tag which supplied it.
+    fn definitions(
+        &self,
+        sections: &[&str],
+        filter_options: &FilterOptions,
+    ) -> IndexMap<&str, Vec<Origin<'_>>> {
+        let mut result: IndexMap<&str, Vec<Origin>> = IndexMap::new();
+
         if let Some(target) = filter_options.target {
             if !sections.contains(&target) {
-                return Vec::new();
+                return result;
             }
         }
-        // let default = ["default".to_string()];
 
-        let tags: Vec<String> = filter_options
-            .as_
-            .clone()
-            .unwrap_or(vec![])
+        let tags = tags(filter_options);
+
+        let matches = |key: &str| {
+            filter_options.glob_match(sections, key)
+                && filter_options.filter_match(sections, key)
+                && filter_options.filter_exclude(sections, key)
+        };
+
+        for (path, document) in self.ancestors() {
+            // Layouts apply before the saucefile's own values, which can override them.
+            if sections == Target::EnvVar.sections() {
+                for (layout, dir) in self.layouts(path, document, &tags) {
+                    if let Some(var) = layout.var().filter(|var| matches(var)) {
+                        result.entry(var).or_default().push(Origin {
+                            path,
+                            tag: None,
+                            value: TargetValue::Literal(dir),
+                        });
+                    }
+                }
+            }
+
+            let values = sections
+                .iter()
+                .filter_map(|section| document[section].as_table())
+                .flat_map(|vars| vars.iter())
+                .filter(|(key, _)| matches(key))
+                .filter_map(|(key, item)| {
+                    TargetValue::select_tagged(item, &tags).map(|var| (key, var))
+                });
+
+            for (key, (value, tag)) in values {
+                result.entry(key).or_default().push(Origin {
+                    path,
+                    tag: tag.map(|t| t.to_string()),
+                    value,
+                });
+            }
+
+            // Keys in the `[unset]` section apply after the saucefile's own values.
+            for section in sections {
+                let (keys, tag) = unset_keys_tagged(&document["unset"][section], &tags);
+                for key in keys.into_iter().filter(|key| matches(key)) {
+                    result.entry(key).or_default().push(Origin {
+                        path,
+                        tag: tag.map(|t| t.to_string()),
+                        value: TargetValue::Unset,
+                    });
+                }
+            }
+        }
+        result
+    }
+
+    /// The `[layout]`s of the saucefile at `path`, along with the absolute directory
+    /// of each.
+    fn layouts(&self, path: &Path, document: &Document, tags: &[String]) -> Vec<(Layout, String)> {
+        let directory = self.directory(path);
+        Layout::from_item(&document["layout"], tags)
             .into_iter()
-            .chain(once("default".to_string()))
-            .collect();
+            .map(|(layout, dir)| (layout, resolve_path_entry(&directory, &dir)))
+            .collect()
+    }
+
+    /// Every definition of each key of the given `target`, along with the saucefile
+    /// and tag which supplied it, ordered such that the last definition is the one
+    /// which takes effect.
+    pub fn origins(
+        &self,
+        target: &Target,
+        filter_options: &FilterOptions,
+    ) -> IndexMap<&str, Vec<Origin<'_>>> {
+        self.definitions(target.sections(), filter_options)
+    }
+
+    /// Every variant of `key` throughout the cascade, irrespective of tags or filters,
+    /// ordered from the outermost saucefile to the innermost.
+    pub fn variants(&self, target: &Target, key: &str) -> Vec<Origin<'_>> {
+        let mut result = Vec::new();
+        for (path, document) in self.ancestors() {
+            let variants = target
+                .sections()
+                .iter()
+                .flat_map(|section| TargetValue::variants(&document[section][key]));
+            for (tag, value) in variants {
+                result.push(Origin {
+                    path,
+                    tag: tag.map(|t| t.to_string()),
+                    value,
+                });
+            }
+
+            let unsets = target
+                .sections()
+                .iter()
+                .flat_map(|section| unset_variants(&document["unset"][section], key));
+            for tag in unsets {
+                result.push(Origin {
+                    path,
+                    tag: tag.map(|t| t.to_string()),
+                    value: TargetValue::Unset,
+                });
+            }
+        }
+        result
+    }
 
-        iproduct!(self.documents(), sections)
-            .filter_map(|(document, section)| document[section].as_table())
-            .flat_map(|vars| vars.iter())
-            .filter(|(key, _)| {
-                filter_options.glob_match(sections, key)
-                    && filter_options.filter_match(sections, key)
-                    && filter_options.filter_exclude(sections, key)
+    fn section(
+        &self,
+        sections: &[&str],
+        filter_options: &FilterOptions,
+    ) -> Vec<(&str, TargetValue)> {
+        self.layers(sections, filter_options)
+            .into_iter()
+            .filter_map(|(key, mut values)| match values.pop() {
+                Some(TargetValue::Unset) | None => None,
+                Some(value) => Some((key, value)),
             })
-            .filter_map(|(key, item)| {
-                for tag in tags.iter() {
-                    let var = match item {
-                        Item::Value(value) => match value {
-                            Value::InlineTable(table) => match table.get(tag) {
-                                Some(value) => unwrap_toml_value(value),
-                                _ => continue,
-                            },
-                            _ => unwrap_toml_value(value),
-                        },
-                        Item::Table(table) => match &table[tag] {
-                            Item::Value(value) => unwrap_toml_value(value),
-                            _ => continue,
-                        },
-                        _ => continue,
-                    };
-                    return Some((key, var));
-                }
-                None
+            .collect()
+    }
+
+    fn interpolator(&self, filter_options: &FilterOptions, output: &Output) -> Interpolator<'_> {
+        Interpolator::new(self.layers(
+            Target::EnvVar.sections(),
+            &FilterOptions {
+                as_: filter_options.as_.clone(),
+                ..Default::default()
+            },
+        ))
+        .show(output.is_show())
+        .tag(
+            filter_options
+                .as_
+                .as_ref()
+                .and_then(|tags| tags.first())
+                .map_or("default", String::as_str),
+        )
+        .template_dir(self.config_dir.clone())
+        .commands(&self.commands)
+    }
+
+    /// Resolves `${NAME}` references (and runs any commands) in the values of the
+    /// given `target`, including aliases and functions, whose shell code can write
+    /// `$${NAME}` for the shell's own `${NAME}`. Values whose commands fail are left
+    /// out.
+    ///
+    /// References are resolved against every environment variable in the cascade,
+    /// irrespective of any filters, such that filtering down to a single value
+    /// still resolves the values it references.
+    fn resolved_section(
+        &self,
+        target: Target,
+        filter_options: &FilterOptions,
+        output: &mut Output,
+    ) -> Vec<(&str, String)> {
+        let interpolator = self.interpolator(filter_options, output);
+
+        let mut errors = IndexSet::new();
+        let result = self
+            .section(target.sections(), filter_options)
+            .into_iter()
+            .filter_map(|(key, value)| {
+                let value = match target {
+                    Target::EnvVar => interpolator.resolve_var(key, &value, &mut errors),
+                    _ => interpolator.interpolate(key, &value, &mut errors),
+                };
+                Some((key, value?))
             })
-            .collect::<IndexMap<&str, String>>()
+            .collect();
+
+        report_errors(errors, output);
+        result
+    }
+
+    /// The keys of the given `target`, without resolving (or running) their values.
+    pub fn keys(&self, target: &Target, filter_options: &FilterOptions) -> Vec<&str> {
+        self.layers(target.sections(), filter_options)
+            .into_keys()
+            .collect()
+    }
+
+    /// The keys of the given `target` which are unset by the innermost saucefile
+    /// defining them.
+    pub fn unset_keys(&self, target: &Target, filter_options: &FilterOptions) -> Vec<&str> {
+        self.layers(target.sections(), filter_options)
             .into_iter()
+            .filter(|(_, values)| values.last() == Some(&TargetValue::Unset))
+            .map(|(key, _)| key)
             .collect()
     }
 
-    pub fn vars(&self, filter_options: &FilterOptions) -> Vec<(&str, String)> {
-        self.section(&["env", "environment"], filter_options)
+    pub fn vars(&self, filter_options: &FilterOptions, output: &mut Output) -> Vec<(&str, String)> {
+        self.resolved_section(Target::EnvVar, filter_options, output)
+    }
+
+    pub fn aliases(
+        &self,
+        filter_options: &FilterOptions,
+        output: &mut Output,
+    ) -> Vec<(&str, String)> {
+        self.resolved_section(Target::Alias, filter_options, output)
     }
 
-    pub fn aliases(&self, filter_options: &FilterOptions) -> Vec<(&str, String)> {
-        self.section(&["alias"], filter_options)
+    pub fn functions(
+        &self,
+        filter_options: &FilterOptions,
+        output: &mut Output,
+    ) -> Vec<(&str, String)> {
+        self.resolved_section(Target::Function, filter_options, output)
     }
 
-    pub fn functions(&self, filter_options: &FilterOptions) -> Vec<(&str, String)> {
-        self.section(&["function"], filter_options)
+    pub fn files(
+        &self,
+        filter_options: &FilterOptions,
+        output: &mut Output,
+    ) -> Vec<(&str, String)> {
+        self.resolved_section(Target::File, filter_options, output)
     }
 
-    pub fn files(&self, filter_options: &FilterOptions) -> Vec<(&str, String)> {
-        self.section(&["file"], filter_options)
+    /// The `[hooks]` command `hook` (i.e. `on_enter` or `on_leave`) of each saucefile,
+    /// in cascade order.
+    ///
+    /// Hooks belong to the saucefile as a whole, rather than any one target, so
+    /// none are run when only some targets' values are asked for.
+    pub fn hooks(
+        &self,
+        hook: &str,
+        filter_options: &FilterOptions,
+        output: &mut Output,
+    ) -> Vec<(&PathBuf, String)> {
+        if filter_options.is_narrowed() {
+            return Vec::new();
+        }
+
+        let tags = tags(filter_options);
+        let interpolator = self.interpolator(filter_options, output);
+        let mut errors = IndexSet::new();
+
+        let result = self
+            .ancestors()
+            .filter_map(|(path, document)| {
+                match TargetValue::select_tagged(&document["hooks"][hook], &tags)? {
+                    (TargetValue::Unset, _) => None,
+                    (value, _) => Some((path, interpolator.verbatim(hook, &value, &mut errors)?)),
+                }
+            })
+            .collect();
+
+        report_errors(errors, output);
+        result
     }
+
+    /// The entries added to list-like variables by each saucefile, in cascade order.
+    ///
+    /// Relative entries are resolved against the directory targeted by the
+    /// saucefile which defines them.
+    pub fn path_entries(
+        &self,
+        filter_options: &FilterOptions,
+        output: &mut Output,
+    ) -> Vec<PathEntries<'_>> {
+        let sections = Target::Path.sections();
+        if let Some(target) = filter_options.target {
+            if !sections.contains(&target) {
+                return Vec::new();
+            }
+        }
+
+        let tags = tags(filter_options);
+        let interpolator = self.interpolator(filter_options, output);
+        let mut errors = IndexSet::new();
+
+        let result = self
+            .ancestors()
+            .flat_map(|(path, document)| {
+                let directory = self.directory(path);
+                // Each layout's bin directory is prepended ahead of the saucefile's own entries.
+                let layouts =
+                    self.layouts(path, document, &tags)
+                        .into_iter()
+                        .map(|(layout, dir)| PathEntries {
+                            origin: path,
+                            var: "PATH",
+                            position: PathPosition::Prepend,
+                            entries: vec![Path::new(&dir)
+                                .join(layout.bin())
+                                .to_string_lossy()
+                                .to_string()],
+                        });
+                let entries = document["path"]
+                    .as_table()
+                    .map(|table| PathEntries::from_table(path, table))
+                    .unwrap_or_default();
+
+                layouts
+                    .chain(entries)
+                    .map(move |path_entries| (directory.clone(), path_entries))
+                    .collect::<Vec<_>>()
+            })
+            .filter(|(_, path_entries)| {
+                filter_options.glob_match(sections, path_entries.var)
+                    && filter_options.filter_match(sections, path_entries.var)
+                    && filter_options.filter_exclude(sections, path_entries.var)
+            })
+            .map(|(directory, mut path_entries)| {
+                path_entries.entries = path_entries
+                    .entries
+                    .iter()
+                    .filter_map(|entry| {
+                        let entry = interpolator.interpolate(
+                            path_entries.var,
+                            &entry.as_str().into(),
+                            &mut errors,
+                        )?;
+                        Some(resolve_path_entry(&directory, &entry))
+                    })
+                    .collect();
+                path_entries
+            })
+            .collect();
+
+        report_errors(errors, output);
+        result
+    }
+}
+
+/// The tags (i.e. `--as`) whose values are selected, in order of preference.
+fn tags(filter_options: &FilterOptions) -> Vec<String> {
+    filter_options
+        .as_
+        .clone()
+        .unwrap_or(vec![])
+        .into_iter()
+        .chain(once("default".to_string()))
+        .collect()
+}
+
+fn resolve_path_entry(directory: &Path, entry: &str) -> String {
+    let path = match (entry.strip_prefix("~/"), etcetera::home_dir()) {
+        (Some(entry), Ok(home)) => home.join(entry),
+        _ => directory.join(entry),
+    };
+    path.absolutize()
+        .map(|p| p.to_string_lossy().to_string())
+        .unwrap_or_else(|_| path.to_string_lossy().to_string())
 }
 
 impl Default for Saucefile {
@@ -139,12 +601,53 @@ impl Default for Saucefile {
             path: Some(PathBuf::new()),
             document: Document::new(),
             ancestors: Vec::new(),
+            directories: HashMap::new(),
+            config_dir: PathBuf::new(),
+            commands: CommandCache::default(),
         }
     }
 }
 
 #[cfg(test)]
 mod tests {
+    mod read {
+        use super::super::*;
+        use crate::test_utils::setup;
+        use pretty_assertions::assert_eq;
+
+        #[test]
+        fn it_stops_at_saucefiles_which_stop_the_cascade() {
+            let (_, _, mut output) = setup();
+            let dir = std::env::temp_dir().join(format!("sauce-cascade-{}", std::process::id()));
+            std::fs::create_dir_all(&dir).unwrap();
+
+            let files = [
+                ("outer.toml", "[env]\nLEAKED = \"1\""),
+                (
+                    "middle.toml",
+                    "[settings]\ncascade = false\n[env]\nMIDDLE = \"1\"",
+                ),
+                ("inner.toml", "[env]\nINNER = \"1\""),
+            ];
+            let paths = files
+                .iter()
+                .map(|(name, content)| {
+                    let path = dir.join(name);
+                    std::fs::write(&path, content).unwrap();
+                    path
+                })
+                .collect::<Vec<_>>();
+
+            let sauce = Saucefile::read(&mut output, paths.clone());
+            assert_eq!(
+                sauce.paths().collect::<Vec<_>>(),
+                vec![&paths[1], &paths[2]]
+            );
+
+            std::fs::remove_dir_all(&dir).ok();
+        }
+    }
+
     mod section {
         use super::super::*;
         use pretty_assertions::assert_eq;
@@ -160,7 +663,7 @@ mod tests {
             sauce.document = toml.parse::<Document>().expect("invalid doc");
 
             let result = sauce.section(&["foo"], &FilterOptions::default());
-            assert_eq!(result, vec![("bar", "baz".to_string())]);
+            assert_eq!(result, vec![("bar", "baz".into())]);
         }
 
         #[test]
@@ -174,7 +677,7 @@ mod tests {
             sauce.document = toml.parse::<Document>().expect("invalid doc");
 
             let result = sauce.section(&["non-matching", "foo"], &FilterOptions::default());
-            assert_eq!(result, vec![("bar", "baz".to_string())]);
+            assert_eq!(result, vec![("bar", "baz".into())]);
         }
 
         #[test]
@@ -204,10 +707,7 @@ mod tests {
             sauce.document = toml.parse::<Document>().expect("invalid doc");
 
             let result = sauce.section(&["foo"], &FilterOptions::default());
-            assert_eq!(
-                result,
-                vec![("bar", "1".to_string()), ("bees", "2".to_string()),]
-            );
+            assert_eq!(result, vec![("bar", "1".into()), ("bees", "2".into()),]);
         }
 
         #[test]
@@ -229,10 +729,7 @@ mod tests {
                     ..Default::default()
                 },
             );
-            assert_eq!(
-                result,
-                vec![("bar", "1".to_string()), ("bees", "2".to_string()),]
-            );
+            assert_eq!(result, vec![("bar", "1".into()), ("bees", "2".into()),]);
         }
 
         #[test]
@@ -259,10 +756,10 @@ mod tests {
             assert_eq!(
                 result,
                 vec![
-                    ("one", "1".to_string()),
-                    ("two", "2".to_string()),
-                    ("three", "3".to_string()),
-                    ("four", "4".to_string()),
+                    ("one", "1".into()),
+                    ("two", "2".into()),
+                    ("three", "3".into()),
+                    ("four", "4".into()),
                 ]
             );
         }
@@ -307,9 +804,128 @@ mod tests {
                     ..Default::default()
                 },
             );
+            assert_eq!(result, vec![("bar", "1".into()), ("bees", "2".into())]);
+        }
+    }
+
+    mod unset_keys {
+        use super::super::*;
+        use pretty_assertions::assert_eq;
+
+        use crate::test_utils::setup;
+
+        #[test]
+        fn it_drops_keys_unset_by_a_descendant() {
+            let (_, _, mut output) = setup();
+            let mut sauce = Saucefile::default();
+
+            let toml = r#"
+            [env]
+            AWS_PROFILE = "work"
+            FOO = "foo"
+            "#;
+            let ancestor = toml.parse::<Document>().expect("invalid doc");
+            sauce.ancestors.push((PathBuf::new(), ancestor));
+
+            sauce.document = "[env]\nAWS_PROFILE = {unset = true}"
+                .parse::<Document>()
+                .expect("invalid doc");
+
+            let filter_options = FilterOptions::default();
+            assert_eq!(
+                sauce.vars(&filter_options, &mut output),
+                vec![("FOO", "foo".to_string())]
+            );
             assert_eq!(
-                result,
-                vec![("bar", "1".to_string()), ("bees", "2".to_string())]
+                sauce.unset_keys(&Target::EnvVar, &filter_options),
+                vec!["AWS_PROFILE"]
+            );
+        }
+
+        #[test]
+        fn it_unsets_keys_from_the_unset_section_per_tag() {
+            let (_, _, mut output) = setup();
+            let mut sauce = Saucefile::default();
+
+            let toml = r#"
+            [alias]
+            g = "git"
+
+            [unset]
+            alias = {ci = ["g"]}
+            "#;
+            sauce.document = toml.parse::<Document>().expect("invalid doc");
+
+            let filter_options = FilterOptions::default();
+            assert_eq!(
+                sauce.aliases(&filter_options, &mut output),
+                vec![("g", "git".to_string())]
+            );
+
+            let filter_options = FilterOptions {
+                as_: Some(vec!["ci".to_string()]),
+                ..Default::default()
+            };
+            assert_eq!(sauce.aliases(&filter_options, &mut output), vec![]);
+            assert_eq!(sauce.unset_keys(&Target::Alias, &filter_options), vec!["g"]);
+        }
+    }
+
+    mod origins {
+        use super::super::*;
+        use pretty_assertions::assert_eq;
+
+        #[test]
+        fn it_records_each_definition_in_the_cascade() {
+            let mut sauce = Saucefile::default();
+
+            let toml = r#"
+            [env]
+            FOO = "foo"
+            BAR = {default = "bar", prod = "prod-bar"}
+            "#;
+            let ancestor = toml.parse::<Document>().expect("invalid doc");
+            sauce.ancestors.push((PathBuf::from("work.toml"), ancestor));
+
+            sauce.document = "[env]\nFOO = \"inner\"\n\n[unset]\nenv = {prod = [\"BAR\"]}"
+                .parse::<Document>()
+                .expect("invalid doc");
+            sauce.path = Some(PathBuf::from("work/repo.toml"));
+
+            let filter_options = FilterOptions {
+                as_: Some(vec!["prod".to_string()]),
+                ..Default::default()
+            };
+            let result = sauce.origins(&Target::EnvVar, &filter_options);
+            assert_eq!(
+                result["FOO"],
+                vec![
+                    Origin {
+                        path: Path::new("work.toml"),
+                        tag: None,
+                        value: "foo".into(),
+                    },
+                    Origin {
+                        path: Path::new("work/repo.toml"),
+                        tag: None,
+                        value: "inner".into(),
+                    },
+                ]
+            );
+            assert_eq!(
+                result["BAR"],
+                vec![
+                    Origin {
+                        path: Path::new("work.toml"),
+                        tag: Some("prod".to_string()),
+                        value: "prod-bar".into(),
+                    },
+                    Origin {
+                        path: Path::new("work/repo.toml"),
+                        tag: Some("prod".to_string()),
+                        value: TargetValue::Unset,
+                    },
+                ]
             );
         }
     }
@@ -318,35 +934,447 @@ mod tests {
         use super::super::*;
         use pretty_assertions::assert_eq;
 
+        use crate::test_utils::setup;
+
         #[test]
         fn it_yields_empty_when_empty() {
+            let (_, _, mut output) = setup();
             let sauce = Saucefile::default();
-            let result = sauce.vars(&FilterOptions::default());
+            let result = sauce.vars(&FilterOptions::default(), &mut output);
             assert_eq!(result, &[]);
         }
+
+        #[test]
+        fn it_interpolates_across_the_cascade() {
+            let (_, err, mut output) = setup();
+            let mut sauce = Saucefile::default();
+
+            let toml = r#"
+            [env]
+            HOST = "localhost"
+            URL = "pg://${HOST}:${PORT}/app"
+            "#;
+            let ancestor = toml.parse::<Document>().expect("invalid doc");
+            sauce.ancestors.push((PathBuf::new(), ancestor));
+
+            let toml = r#"
+            [env]
+            PORT = 5432
+            HOST = "${HOST}.local"
+            "#;
+            sauce.document = toml.parse::<Document>().expect("invalid doc");
+
+            let result = sauce.vars(&FilterOptions::default(), &mut output);
+            assert_eq!(
+                result,
+                vec![
+                    ("HOST", "localhost.local".to_string()),
+                    ("URL", "pg://localhost.local:5432/app".to_string()),
+                    ("PORT", "5432".to_string()),
+                ]
+            );
+            assert_eq!(err.value(), "");
+        }
+
+        #[test]
+        fn it_resolves_references_excluded_by_filters() {
+            let (_, _, mut output) = setup();
+            let mut sauce = Saucefile::default();
+
+            let toml = r#"
+            [env]
+            HOST = {default = "localhost", prod = "example.com"}
+            URL = "https://${HOST}"
+            "#;
+            sauce.document = toml.parse::<Document>().expect("invalid doc");
+
+            let result = sauce.vars(
+                &FilterOptions {
+                    filters: &[(None, "URL")],
+                    as_: Some(vec!["prod".to_string()]),
+                    ..Default::default()
+                },
+                &mut output,
+            );
+            assert_eq!(result, vec![("URL", "https://example.com".to_string())]);
+        }
+
+        #[test]
+        fn it_reports_unresolved_references() {
+            let (_, err, mut output) = setup();
+            let mut sauce = Saucefile::default();
+
+            let toml = r#"
+            [env]
+            URL = "https://${SAUCE_TEST_UNRESOLVED_HOST}"
+            "#;
+            sauce.document = toml.parse::<Document>().expect("invalid doc");
+
+            let result = sauce.vars(&FilterOptions::default(), &mut output);
+            assert_eq!(
+                result,
+                vec![("URL", "https://${SAUCE_TEST_UNRESOLVED_HOST}".to_string())]
+            );
+            assert_eq!(
+                err.value(),
+                "Unresolved reference ${SAUCE_TEST_UNRESOLVED_HOST} in URL\n"
+            );
+            assert_eq!(output.error_code(), Some(3));
+        }
+    }
+
+    mod commands {
+        use super::super::*;
+        use crate::test_utils::setup;
+        use pretty_assertions::assert_eq;
+
+        #[test]
+        fn it_runs_tagged_commands() {
+            let (_, _, mut output) = setup();
+            let mut sauce = Saucefile::default();
+
+            let toml = r#"
+            [env]
+            TOKEN = {default = "none", prod = {cmd = "echo secret"}}
+            "#;
+            sauce.document = toml.parse::<Document>().expect("invalid doc");
+
+            let filter_options = FilterOptions {
+                as_: Some(vec!["prod".to_string()]),
+                ..Default::default()
+            };
+            let result = sauce.vars(&filter_options, &mut output);
+            assert_eq!(result, vec![("TOKEN", "secret".to_string())]);
+
+            output.set_show(true);
+            let result = sauce.vars(&filter_options, &mut output);
+            assert_eq!(result, vec![("TOKEN", "$(echo secret)".to_string())]);
+        }
+
+        #[test]
+        fn it_leaves_out_values_whose_commands_fail() {
+            let (_, err, mut output) = setup();
+            let mut sauce = Saucefile::default();
+
+            let toml = r#"
+            [env]
+            TOKEN = {cmd = "exit 1"}
+            URL = "https://${TOKEN}@example.com"
+            HOST = "example.com"
+            "#;
+            sauce.document = toml.parse::<Document>().expect("invalid doc");
+
+            let result = sauce.vars(&FilterOptions::default(), &mut output);
+            assert_eq!(result, vec![("HOST", "example.com".to_string())]);
+            assert_eq!(err.value().contains("failed: exited with code 1"), true);
+        }
+    }
+
+    mod path_entries {
+        use super::super::*;
+        use crate::{test_utils::setup, value::PathPosition};
+        use pretty_assertions::assert_eq;
+
+        #[test]
+        fn it_resolves_entries_against_each_saucefiles_directory() {
+            let (_, _, mut output) = setup();
+            let mut sauce = Saucefile::default();
+
+            let toml = r#"
+            [path]
+            append = ["/usr/local/bin", "../bin"]
+            "#;
+            let ancestor = toml.parse::<Document>().expect("invalid doc");
+            sauce.ancestors.push((PathBuf::from("work.toml"), ancestor));
+            sauce
+                .directories
+                .insert(PathBuf::from("work.toml"), PathBuf::from("/home/work"));
+
+            let toml = r#"
+            [path]
+            prepend = ["./node_modules/.bin"]
+            "#;
+            sauce.document = toml.parse::<Document>().expect("invalid doc");
+            sauce.path = Some(PathBuf::from("work/repo.toml"));
+            sauce.directories.insert(
+                PathBuf::from("work/repo.toml"),
+                PathBuf::from("/home/work/repo"),
+            );
+
+            let result = sauce.path_entries(&FilterOptions::default(), &mut output);
+            assert_eq!(
+                result,
+                vec![
+                    PathEntries {
+                        origin: Path::new("work.toml"),
+                        var: "PATH",
+                        position: PathPosition::Append,
+                        entries: vec!["/usr/local/bin".to_string(), "/home/bin".to_string()],
+                    },
+                    PathEntries {
+                        origin: Path::new("work/repo.toml"),
+                        var: "PATH",
+                        position: PathPosition::Prepend,
+                        entries: vec!["/home/work/repo/node_modules/.bin".to_string()],
+                    },
+                ]
+            );
+        }
+    }
+
+    mod layouts {
+        use super::super::*;
+        use crate::{test_utils::setup, value::PathPosition};
+        use pretty_assertions::assert_eq;
+
+        fn saucefile() -> Saucefile {
+            let mut sauce = Saucefile::default();
+            let toml = r#"
+            [layout]
+            python = true
+            node = true
+
+            [environment]
+            VIRTUAL_ENV = { ci = "/ci/venv" }
+            "#;
+            sauce.document = toml.parse::<Document>().expect("invalid doc");
+            sauce.path = Some(PathBuf::from("repo.toml"));
+            sauce
+                .directories
+                .insert(PathBuf::from("repo.toml"), PathBuf::from("/home/repo"));
+            sauce
+        }
+
+        #[test]
+        fn it_sets_layout_vars() {
+            let (_, _, mut output) = setup();
+            let sauce = saucefile();
+
+            let result = sauce.vars(&FilterOptions::default(), &mut output);
+            assert_eq!(
+                result,
+                vec![("VIRTUAL_ENV", "/home/repo/.venv".to_string())]
+            );
+
+            let options = FilterOptions {
+                as_: Some(vec!["ci".to_string()]),
+                ..Default::default()
+            };
+            let result = sauce.vars(&options, &mut output);
+            assert_eq!(result, vec![("VIRTUAL_ENV", "/ci/venv".to_string())]);
+        }
+
+        #[test]
+        fn it_prepends_layout_bins() {
+            let (_, _, mut output) = setup();
+            let sauce = saucefile();
+
+            let result = sauce.path_entries(&FilterOptions::default(), &mut output);
+            assert_eq!(
+                result,
+                vec![
+                    PathEntries {
+                        origin: Path::new("repo.toml"),
+                        var: "PATH",
+                        position: PathPosition::Prepend,
+                        entries: vec!["/home/repo/.venv/bin".to_string()],
+                    },
+                    PathEntries {
+                        origin: Path::new("repo.toml"),
+                        var: "PATH",
+                        position: PathPosition::Prepend,
+                        entries: vec!["/home/repo/node_modules/.bin".to_string()],
+                    },
+                ]
+            );
+        }
     }
 
     mod aliases {
         use super::super::*;
         use pretty_assertions::assert_eq;
 
+        use crate::test_utils::setup;
+
         #[test]
         fn it_yields_empty_when_empty() {
+            let (_, _, mut output) = setup();
             let sauce = Saucefile::default();
-            let result = sauce.aliases(&FilterOptions::default());
+            let result = sauce.aliases(&FilterOptions::default(), &mut output);
             assert_eq!(result, &[]);
         }
+
+        #[test]
+        fn it_interpolates_aliases() {
+            let (_, err, mut output) = setup();
+            let mut sauce = Saucefile::default();
+
+            let toml = r#"
+            [environment]
+            DB = "app"
+
+            [alias]
+            db = "psql ${DB}"
+            "#;
+            sauce.document = toml.parse::<Document>().expect("invalid doc");
+
+            let result = sauce.aliases(&FilterOptions::default(), &mut output);
+            assert_eq!(result, vec![("db", "psql app".to_string())]);
+            assert_eq!(err.value(), "");
+        }
     }
 
     mod functions {
         use super::super::*;
         use pretty_assertions::assert_eq;
 
+        use crate::test_utils::setup;
+
         #[test]
         fn it_yields_empty_when_empty() {
+            let (_, _, mut output) = setup();
             let sauce = Saucefile::default();
-            let result = sauce.functions(&FilterOptions::default());
+            let result = sauce.functions(&FilterOptions::default(), &mut output);
             assert_eq!(result, &[]);
         }
+
+        #[test]
+        fn it_interpolates_function_bodies() {
+            let (_, err, mut output) = setup();
+            let mut sauce = Saucefile::default();
+
+            let toml = r#"
+            [environment]
+            HOST = "localhost"
+
+            [function]
+            greet = 'for name in "$@"; do echo "$${name}@${HOST}"; done'
+            "#;
+            sauce.document = toml.parse::<Document>().expect("invalid doc");
+
+            let result = sauce.functions(&FilterOptions::default(), &mut output);
+            assert_eq!(
+                result,
+                vec![(
+                    "greet",
+                    r#"for name in "$@"; do echo "${name}@localhost"; done"#.to_string()
+                )]
+            );
+            assert_eq!(err.value(), "");
+        }
+    }
+
+    mod resolve_settings {
+        use super::super::*;
+        use pretty_assertions::assert_eq;
+
+        #[test]
+        fn it_inherits_settings_through_the_cascade() {
+            let mut sauce = Saucefile::default();
+            let ancestor = "[settings]\nautoload = true\nautoload-args = \"--as dev\""
+                .parse::<Document>()
+                .expect("invalid doc");
+            sauce.ancestors.push((PathBuf::from("work.toml"), ancestor));
+            sauce.document = "[settings]\nautoload-args = \"--as ci\""
+                .parse::<Document>()
+                .expect("invalid doc");
+            sauce.path = Some(PathBuf::from("work/repo.toml"));
+
+            let global = Settings {
+                autoload: Some(false),
+                autoload_hook: Some(true),
+                autoload_args: Some("--as global".to_string()),
+                ..Default::default()
+            };
+            let settings = sauce.resolve_settings(&global);
+            assert_eq!(settings.autoload, true);
+            assert_eq!(settings.autoload_hook, true);
+            // Only read from the global config, when the shell is initialized.
+            assert_eq!(settings.autoload_args, "--as global");
+        }
+    }
+
+    mod use_profiles {
+        use super::super::*;
+        use crate::test_utils::setup;
+        use pretty_assertions::assert_eq;
+
+        /// A config dir containing the given profiles.
+        fn config_dir(name: &str, profiles: &[(&str, &str)]) -> PathBuf {
+            let dir = std::env::temp_dir().join(format!("sauce-{}-{}", name, std::process::id()));
+            std::fs::create_dir_all(dir.join("profiles")).unwrap();
+            for (profile, content) in profiles {
+                std::fs::write(
+                    dir.join("profiles").join(format!("{}.toml", profile)),
+                    content,
+                )
+                .unwrap();
+            }
+            dir
+        }
+
+        #[test]
+        fn it_includes_profiles_ahead_of_the_saucefile() {
+            let (_, _, mut output) = setup();
+            let dir = config_dir(
+                "profiles",
+                &[
+                    ("aws", "[settings]\nuse = [\"base\"]\n[env]\nAWS_PROFILE = \"dev\"\nREGION = \"eu\""),
+                    ("base", "[env]\nBASE = \"1\"\nREGION = \"us\""),
+                ],
+            );
+
+            let mut sauce = Saucefile {
+                config_dir: dir.clone(),
+                ..Default::default()
+            };
+            sauce.document = "[settings]\nuse = [\"aws\"]\n[env]\nAWS_PROFILE = \"prod\""
+                .parse::<Document>()
+                .unwrap();
+            sauce.use_profiles(&mut output);
+
+            let result = sauce.vars(&FilterOptions::default(), &mut output);
+            assert_eq!(
+                result,
+                vec![
+                    ("BASE", "1".to_string()),
+                    ("REGION", "eu".to_string()),
+                    ("AWS_PROFILE", "prod".to_string()),
+                ]
+            );
+
+            std::fs::remove_dir_all(&dir).ok();
+        }
+
+        #[test]
+        fn it_reports_cycles_and_missing_profiles() {
+            let (_, err, mut output) = setup();
+            let dir = config_dir(
+                "cycles",
+                &[
+                    ("a", "[settings]\nuse = [\"b\"]"),
+                    ("b", "[settings]\nuse = [\"a\"]"),
+                ],
+            );
+
+            let mut sauce = Saucefile {
+                config_dir: dir.clone(),
+                ..Default::default()
+            };
+            sauce.document = "[settings]\nuse = [\"a\", \"missing\", \"../profiles/a\"]"
+                .parse::<Document>()
+                .unwrap();
+            sauce.use_profiles(&mut output);
+
+            assert_eq!(sauce.ancestors.len(), 2);
+            assert_eq!(err.value().contains("Profile cycle a -> b -> a"), true);
+            assert_eq!(err.value().contains("No profile named missing"), true);
+            assert_eq!(
+                err.value().contains("Invalid profile name ../profiles/a"),
+                true
+            );
+
+            std::fs::remove_dir_all(&dir).ok();
+        }
     }
 }
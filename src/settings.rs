@@ -16,6 +16,49 @@ pub struct RealizedSettings {
     pub autoload: bool,
     pub autoload_args: String,
     pub clear_ignore: Vec<String>,
+    pub file_mode: Option<u32>,
+}
+
+impl RealizedSettings {
+    /// The settings which are resolved against the global config (and, except for
+    /// `autoload-hook` and `autoload-args`, inherited through the cascade), as
+    /// opposed to those which only apply to the file defining them.
+    pub const NAMES: [&'static str; 5] = [
+        "autoload-hook",
+        "autoload",
+        "autoload-args",
+        "clear-ignore",
+        "file-mode",
+    ];
+
+    /// Each setting (per [`RealizedSettings::NAMES`]) and its value, as written in
+    /// a config file.
+    pub fn values(&self) -> Vec<(&'static str, String)> {
+        let values = [
+            toml_edit::value(self.autoload_hook),
+            toml_edit::value(self.autoload),
+            toml_edit::value(self.autoload_args.as_str()),
+            toml_edit::value(
+                self.clear_ignore
+                    .iter()
+                    .map(|v| v.as_str())
+                    .collect::<Value>(),
+            ),
+            match self.file_mode {
+                Some(mode) => toml_edit::value(format!("{:o}", mode)),
+                None => Item::None,
+            },
+        ];
+
+        Self::NAMES
+            .into_iter()
+            .zip(values)
+            .map(|(name, value)| {
+                let value = value.as_value().map(|v| v.to_string());
+                (name, value.unwrap_or_default().trim().to_string())
+            })
+            .collect()
+    }
 }
 
 #[derive(Debug)]
@@ -25,6 +68,15 @@ pub struct Settings {
     pub autoload: Option<bool>,
     pub autoload_args: Option<String>,
     pub clear_ignore: Option<Vec<String>>,
+    /// The (octal) permission bits with which `[file]` targets are written.
+    pub file_mode: Option<u32>,
+    /// The profiles (i.e. `use`) whose targets are included ahead of the saucefile's
+    /// own values. Unlike other settings, this only applies to the file defining it.
+    pub profiles: Option<Vec<String>>,
+    /// Whether the saucefiles above this one are included, i.e. `cascade = false`
+    /// (or equivalently `root = true`) stops the cascade. Like `profiles`, this only
+    /// applies to the file defining it.
+    pub cascade: Option<bool>,
 }
 
 impl Settings {
@@ -41,6 +93,11 @@ impl Settings {
         let autoload = Setting::new(general, "autoload").as_bool();
         let autoload_args = Setting::new(general, "autoload-args").as_string();
         let clear_ignore = Setting::new(general, "clear-ignore").as_vec_of_string();
+        let file_mode = Setting::new(general, "file-mode").as_mode();
+        let profiles = Setting::new(general, "use").as_vec_of_string();
+        let cascade = Setting::new(general, "cascade")
+            .as_bool()
+            .or_else(|| Setting::new(general, "root").as_bool().map(|root| !root));
 
         Self {
             file,
@@ -48,15 +105,22 @@ impl Settings {
             autoload,
             autoload_args,
             clear_ignore,
+            file_mode,
+            profiles,
+            cascade,
         }
     }
 
     pub fn resolve_precedence<'a>(&'a self, fallback: &'a Self) -> RealizedSettings {
-        let mut default = RealizedSettings::default();
+        Self::resolve([fallback, self])
+    }
 
-        let settings_precedence = vec![fallback, self];
+    /// Resolves each setting to its value in the last of `settings` which sets it,
+    /// i.e. `settings` are given in increasing order of precedence.
+    pub fn resolve<'a>(settings: impl IntoIterator<Item = &'a Self>) -> RealizedSettings {
+        let mut default = RealizedSettings::default();
 
-        for settings in settings_precedence {
+        for settings in settings {
             if let Some(v) = settings.autoload_hook {
                 default.autoload_hook = v;
             }
@@ -69,10 +133,25 @@ impl Settings {
             if let Some(v) = &settings.clear_ignore {
                 default.clear_ignore = v.to_vec();
             }
+            if let Some(v) = settings.file_mode {
+                default.file_mode = Some(v);
+            }
         }
         default
     }
 
+    /// Whether `self` sets the setting `name`, i.e. one of [`RealizedSettings::NAMES`].
+    pub fn is_set(&self, name: &str) -> bool {
+        match name {
+            "autoload-hook" => self.autoload_hook.is_some(),
+            "autoload" => self.autoload.is_some(),
+            "autoload-args" => self.autoload_args.is_some(),
+            "clear-ignore" => self.clear_ignore.is_some(),
+            "file-mode" => self.file_mode.is_some(),
+            _ => false,
+        }
+    }
+
     pub fn set_values<T: AsRef<str>>(&self, pairs: &[(T, T)], output: &mut Output) {
         let mut document = get_document(&self.file, output);
         let settings_section = document.as_table_mut().entry("settings");
@@ -83,7 +162,23 @@ impl Settings {
         let values = pairs
             .iter()
             .filter_map(|(setting, value)| match setting.as_ref() {
-                "autoload" | "autoload-hook" | "autoload-args" | "clear-ignore" => {
+                // Given as a string, so that it's not mistaken for a decimal integer.
+                "file-mode" => {
+                    if parse_mode(value.as_ref()).is_some() {
+                        Some((setting.as_ref(), toml_edit::value(value.as_ref())))
+                    } else {
+                        output.notify_error(
+                            ErrorCode::ParseError,
+                            &[
+                                RED.bold().paint("Could not parse config value"),
+                                YELLOW.bold().paint(value.as_ref()),
+                            ],
+                        );
+                        None
+                    }
+                }
+                "autoload" | "autoload-hook" | "autoload-args" | "clear-ignore" | "cascade"
+                | "root" => {
                     if let Ok(parsed_value) = value.as_ref().parse::<Value>() {
                         Some((setting.as_ref(), toml_edit::value(parsed_value)))
                     } else {
@@ -114,7 +209,7 @@ impl Settings {
             return;
         }
 
-        output.write_toml(&self.file, &mut document, "settings", values);
+        output.write_toml(&self.file, &mut document, "settings", values, None);
     }
 }
 
@@ -161,6 +256,15 @@ impl<'a> Setting<'a> {
         }
     }
 
+    pub fn as_mode(&self) -> Option<u32> {
+        if let Some(value) = self.get_value() {
+            let value = value.as_str().and_then(parse_mode);
+            self.notify_invalid("octal file mode", value)
+        } else {
+            None
+        }
+    }
+
     fn notify_invalid<T: Display>(&self, kind: &str, value: Option<T>) -> Option<T> {
         if value.is_none() {
             eprintln!(
@@ -192,6 +296,14 @@ impl<'a> Setting<'a> {
     }
 }
 
+/// Parses octal permission bits, i.e. `"600"`, `"0600"` or `"0o600"`.
+fn parse_mode(value: &str) -> Option<u32> {
+    let digits = value.strip_prefix("0o").unwrap_or(value);
+    u32::from_str_radix(digits, 8)
+        .ok()
+        .filter(|mode| *mode <= 0o7777)
+}
+
 impl Default for Settings {
     fn default() -> Self {
         Self {
@@ -200,6 +312,9 @@ impl Default for Settings {
             autoload: None,
             autoload_args: None,
             clear_ignore: None,
+            file_mode: None,
+            profiles: None,
+            cascade: None,
         }
     }
 }
@@ -232,6 +347,28 @@ mod tests {
             assert_eq!(settings.autoload, Some(true));
             assert_eq!(settings.autoload_hook, Some(true));
         }
+
+        #[test]
+        fn it_loads_octal_file_modes() {
+            let toml = r#"
+                [settings]
+                file-mode = "0600"
+            "#;
+            let doc = toml.parse::<Document>().expect("invalid doc");
+            let settings = Settings::from_document(PathBuf::new(), &doc);
+            assert_eq!(settings.file_mode, Some(0o600));
+        }
+
+        #[test]
+        fn it_treats_root_as_stopping_the_cascade() {
+            let toml = r#"
+                [settings]
+                root = true
+            "#;
+            let doc = toml.parse::<Document>().expect("invalid doc");
+            let settings = Settings::from_document(PathBuf::new(), &doc);
+            assert_eq!(settings.cascade, Some(false));
+        }
     }
 
     mod settings_resolve_precedence {
@@ -1,13 +1,19 @@
-use std::path::Path;
+use std::iter::once;
+use std::path::{Path, PathBuf};
+
+use indexmap::IndexMap;
 
 use crate::{
     colors::{BLUE, RED, YELLOW},
+    export::{Export, ExportFormat},
     filter::{parse_match_option, FilterOptions},
-    output::Output,
+    output::{ErrorCode, Output},
     saucefile::Saucefile,
     settings::Settings,
     shell::Shell,
+    state::{digest, LoadedHook, LoadedPathEntries, LoadedState},
     target::Target,
+    value::{Origin, PathEntries, PathPosition, TargetValue},
 };
 
 pub fn edit(output: &mut Output, shell: &dyn Shell, path: &Path) {
@@ -44,15 +50,203 @@ pub fn execute_shell_command(output: &mut Output, shell: &dyn Shell, command: &s
     }
 }
 
+/// Runs `command` directly (rather than through a shell), with the resolved env
+/// vars and `[path]` entries applied to its environment.
+///
+/// With `pure`, the command starts from an empty environment, rather than the
+/// current one.
+pub fn exec(
+    output: &mut Output,
+    saucefile: &Saucefile,
+    filter_options: &FilterOptions,
+    command: &[String],
+    pure: bool,
+) {
+    let vars = saucefile.vars(filter_options, output);
+    let unset = saucefile.unset_keys(&Target::EnvVar, filter_options);
+    let path_entries = saucefile.path_entries(filter_options, output);
+
+    let current = |var: &str| if pure { None } else { std::env::var(var).ok() };
+    let environment = child_environment(&vars, &unset, &path_entries, current);
+
+    let (program, args) = match command.split_first() {
+        Some(split) => split,
+        None => return,
+    };
+
+    if output.is_show() {
+        let mut lines = environment
+            .iter()
+            .map(|(name, value)| match value {
+                Some(value) => format!("{}={}", name, value),
+                None => format!("unset {}", name),
+            })
+            .collect::<Vec<_>>();
+        lines.push(command.join(" "));
+        output.notify_str(&(lines.join("\n") + "\n"));
+        return;
+    }
+
+    let mut exec = subprocess::Exec::cmd(program).args(args);
+    if pure {
+        exec = exec.env_clear();
+    }
+    for (name, value) in environment.iter() {
+        exec = match value {
+            Some(value) => exec.env(name, value),
+            None => exec.env_remove(name),
+        };
+    }
+
+    propagate_exit_status(output, program, exec.join());
+}
+
+/// Runs the `[function]` named `function` as a task, in a (non-interactive) subshell
+/// of the given `shell`, with the resolved env vars, `[path]` entries and every
+/// other function defined.
+pub fn run(
+    output: &mut Output,
+    shell: &dyn Shell,
+    saucefile: &Saucefile,
+    filter_options: &FilterOptions,
+    function: &str,
+    args: &[String],
+) {
+    let functions = saucefile.functions(filter_options, output);
+    if !functions.iter().any(|(name, _)| *name == function) {
+        output.notify_error(
+            ErrorCode::CommandError,
+            &[RED.paint("No function found for "), YELLOW.paint(function)],
+        );
+        return;
+    }
+
+    let vars = saucefile.vars(filter_options, output);
+    let unset = saucefile.unset_keys(&Target::EnvVar, filter_options);
+    let path_entries = saucefile.path_entries(filter_options, output);
+
+    let script = shell.script();
+    let shell = script.as_deref().unwrap_or(shell);
+
+    let mut lines = Vec::new();
+    lines.extend(unset.iter().map(|k| shell.unset_var(k)));
+    lines.extend(vars.iter().map(|(k, v)| shell.set_var(k, v)));
+    lines.extend(
+        path_entries
+            .iter()
+            .map(|p| shell.add_path(p.var, p.position, &p.entries)),
+    );
+    lines.extend(functions.iter().map(|(k, v)| shell.set_function(k, v)));
+
+    let call = once(function.to_string())
+        .chain(args.iter().map(|arg| shell.quote(arg)))
+        .collect::<Vec<_>>();
+    lines.push(call.join(" "));
+    let script = lines.join("\n");
+
+    if output.is_show() {
+        output.notify_str(&(script + "\n"));
+        return;
+    }
+
+    let result = subprocess::Exec::cmd(shell.name())
+        .arg("-c")
+        .arg(script)
+        .join();
+    propagate_exit_status(output, shell.name(), result);
+}
+
+/// Lists the names of the `[function]` entries which can be `run` as tasks, one per
+/// line, such that they can be consumed by scripts (i.e. completions).
+pub fn list_functions(output: &mut Output, saucefile: &Saucefile, filter_options: &FilterOptions) {
+    // An unset function can't be run, so it isn't listed.
+    let unset = saucefile.unset_keys(&Target::Function, filter_options);
+    let functions = saucefile
+        .keys(&Target::Function, filter_options)
+        .into_iter()
+        .filter(|k| !unset.contains(k))
+        .collect::<Vec<_>>();
+    if functions.is_empty() {
+        output.notify(&[YELLOW.paint("No functions found")]);
+        return;
+    }
+    output.output(functions.join("\n"));
+}
+
+/// Exits with the status of the `program` run on the user's behalf, where (per
+/// shell convention) being killed by a signal is reported as 128 + the signal.
+fn propagate_exit_status(
+    output: &mut Output,
+    program: &str,
+    result: subprocess::Result<subprocess::ExitStatus>,
+) {
+    match result {
+        Ok(subprocess::ExitStatus::Exited(code)) => output.set_exit_code(code as i32),
+        Ok(subprocess::ExitStatus::Signaled(signal)) => output.set_exit_code(128 + signal as i32),
+        Ok(_) => output.set_exit_code(1),
+        Err(error) => {
+            output.notify_error(
+                ErrorCode::CommandError,
+                &[
+                    RED.bold().paint("Couldn't run "),
+                    YELLOW.paint(program),
+                    RED.bold().paint(format!(": {}", error)),
+                ],
+            );
+        }
+    }
+}
+
+/// The changes to make to a child process' environment, where `None` removes the
+/// variable, and `current` supplies the existing value of list-like variables
+/// (i.e. `PATH`) to which `[path]` entries are added.
+fn child_environment<F>(
+    vars: &[(&str, String)],
+    unset: &[&str],
+    path_entries: &[PathEntries],
+    current: F,
+) -> IndexMap<String, Option<String>>
+where
+    F: Fn(&str) -> Option<String>,
+{
+    let mut result: IndexMap<String, Option<String>> = IndexMap::new();
+    for name in unset {
+        result.insert(name.to_string(), None);
+    }
+    for (name, value) in vars {
+        result.insert(name.to_string(), Some(value.clone()));
+    }
+
+    for path_entries in path_entries {
+        let var = path_entries.var;
+        let existing = match result.get(var) {
+            Some(value) => value.clone(),
+            None => current(var),
+        };
+        let existing = existing.filter(|e| !e.is_empty());
+
+        let entries = path_entries.entries.join(":");
+        let value = match (path_entries.position, existing) {
+            (_, None) => entries,
+            (PathPosition::Prepend, Some(existing)) => format!("{}:{}", entries, existing),
+            (PathPosition::Append, Some(existing)) => format!("{}:{}", existing, entries),
+        };
+        result.insert(var.to_string(), Some(value));
+    }
+    result
+}
+
+/// Unloads the values of `saucefile`, whose `[file]` targets are relative to `dir`.
 pub fn clear(
     output: &mut Output,
     shell: &dyn Shell,
     saucefile: &Saucefile,
     global_settings: &Settings,
     filter_options: &FilterOptions,
+    dir: &Path,
+    loaded: &LoadedState,
 ) {
-    let local_settings = saucefile.settings();
-    let settings = local_settings.resolve_precedence(global_settings);
+    let settings = saucefile.resolve_settings(global_settings);
     let filter_exclusions = settings
         .clear_ignore
         .iter()
@@ -64,57 +258,496 @@ pub fn clear(
         ..filter_options.clone()
     };
 
-    output.output(render_items(saucefile.vars(&filter_options), |k, _| {
-        shell.unset_var(k)
+    let keys = |target| saucefile.keys(&target, &filter_options);
+    output.output(render_keys(keys(Target::EnvVar), |k| shell.restore_var(k)));
+    output.output(render_keys(keys(Target::Alias), |k| shell.restore_alias(k)));
+    output.output(render_keys(keys(Target::Function), |k| {
+        shell.restore_function(k)
     }));
-    output.output(render_items(saucefile.aliases(&filter_options), |k, _| {
-        shell.unset_alias(k)
+
+    // Only the files which sauce wrote are removed (restoring whatever they replaced),
+    // wherever they were written from, rather than whatever happens to exist now.
+    let file_keys = keys(Target::File);
+    let files = loaded
+        .files
+        .iter()
+        .filter(|f| file_keys.iter().any(|k| Path::new(f).ends_with(k)))
+        .cloned()
+        .collect::<Vec<_>>();
+    remove_files(output, files.iter());
+
+    let path_entries = saucefile.path_entries(&filter_options, output);
+    // Only the keys of the cleared values matter, so their values aren't resolved.
+    let vars = keys(Target::EnvVar)
+        .into_iter()
+        .map(|k| (k, String::new()))
+        .collect::<Vec<_>>();
+    // The hooks which run are those recorded when the saucefiles were loaded, as with
+    // unloading, rather than whatever the saucefiles currently say.
+    let paths = saucefile.paths().collect::<Vec<_>>();
+    let on_leave = loaded
+        .on_leave
+        .iter()
+        .filter(|h| !filter_options.is_narrowed() && paths.contains(&&h.path))
+        .cloned()
+        .collect::<Vec<_>>();
+    let mut cleared = loaded_state(saucefile, &filter_options, dir, &vars, &path_entries, &[]);
+    cleared.files = files;
+    output.output(render_path_entries(path_entries, |p| {
+        shell.remove_path(p.var, p.position, &p.entries)
     }));
-    output.output(render_items(
-        saucefile.functions(&filter_options),
-        |k, _| shell.unset_function(k),
+    output.output(render_hooks(
+        shell,
+        "on_leave",
+        on_leave.iter().rev().map(|h| &h.hook),
+    ));
+    cleared.on_leave = on_leave;
+
+    output.output(render_loaded_state(
+        shell,
+        loaded,
+        &loaded.without(&cleared),
     ));
-    output.output(render_items(saucefile.files(&filter_options), |k, _| {
-        shell.unset_file(k)
-    }));
     output.notify(&[BLUE.bold().paint("Cleared your sauce")]);
 }
 
+/// Displays the resolved values of `target`.
+///
+/// Given `origin` (the data directory, against which saucefile paths are shown),
+/// the saucefile and tag which supplied each value are included, along with any
+/// values it shadowed further up the cascade.
 pub fn show(
     output: &mut Output,
     filter_options: &FilterOptions,
     target: Target,
     saucefile: &Saucefile,
+    origin: Option<&Path>,
 ) {
-    let header = match target {
+    let header: &[&str] = match target {
         Target::EnvVar => &["Variable", "Value"],
         Target::Alias => &["Alias", "Value"],
         Target::Function => &["Function", "Body"],
         Target::File => &["File", "Content"],
+        Target::Path => &["Variable", "Entries"],
     };
 
+    let mut origins = Vec::new();
     let pairs = match target {
-        Target::EnvVar => saucefile.vars(filter_options),
-        Target::Alias => saucefile.aliases(filter_options),
-        Target::Function => saucefile.functions(filter_options),
-        Target::File => saucefile.files(filter_options),
+        Target::EnvVar => saucefile.vars(filter_options, output),
+        Target::Alias => saucefile.aliases(filter_options, output),
+        Target::Function => saucefile.functions(filter_options, output),
+        Target::File => saucefile.files(filter_options, output),
+        Target::Path => saucefile
+            .path_entries(filter_options, output)
+            .into_iter()
+            .map(|p| {
+                if let Some(data_dir) = origin {
+                    origins.push((format_path(p.origin, data_dir), String::new()));
+                }
+                let entries = p
+                    .entries
+                    .iter()
+                    .map(|e| format!("{} {}", p.position.name(), e));
+                (p.var, entries.collect::<Vec<_>>().join("\n"))
+            })
+            .collect(),
     };
     let preset = match target {
         Target::EnvVar => None,
         Target::Alias => None,
         Target::Function => Some("││──╞═╪╡│ │││┬┴┌┐└┘"),
         Target::File => Some("││──╞═╪╡│ │││┬┴┌┐└┘"),
+        Target::Path => Some("││──╞═╪╡│ │││┬┴┌┐└┘"),
     };
 
+    let mut header = header.to_vec();
+    if let Some(data_dir) = origin {
+        header.extend(["Origin", "Shadowed"]);
+
+        if !matches!(target, Target::Path) {
+            let definitions = saucefile.origins(&target, filter_options);
+            origins = pairs
+                .iter()
+                .map(|(key, _)| {
+                    let mut definitions = definitions.get(key).cloned().unwrap_or_default();
+                    let winner = definitions
+                        .pop()
+                        .map(|o| format_origin(&o, data_dir))
+                        .unwrap_or_default();
+                    let shadowed = definitions
+                        .iter()
+                        .map(|o| {
+                            let value = match o.value {
+                                TargetValue::Unset => "(unset)".to_string(),
+                                ref value => value.to_string(),
+                            };
+                            format!("{} from {}", value, format_origin(o, data_dir))
+                        })
+                        .collect::<Vec<_>>()
+                        .join("\n");
+                    (winner, shadowed)
+                })
+                .collect();
+        }
+    }
+
     let cells = pairs
         .iter()
-        .map(|(k, v)| vec![<&str>::clone(k), v])
+        .enumerate()
+        .map(|(i, (k, v))| {
+            let mut row = vec![<&str>::clone(k), v.as_str()];
+            if let Some((winner, shadowed)) = origins.get(i) {
+                row.extend([winner.as_str(), shadowed.as_str()]);
+            }
+            row
+        })
+        .collect::<Vec<_>>();
+    let table = output.format_table(&header, cells, preset);
+
+    output.notify_str(&table);
+}
+
+/// Displays the effective value of each setting, along with the file (i.e. the
+/// global config, or a saucefile in the cascade) which set it.
+pub fn show_settings(
+    output: &mut Output,
+    saucefile: &Saucefile,
+    global_settings: &Settings,
+    data_dir: &Path,
+) {
+    let cascade = saucefile.cascade_settings();
+    let precedence = once(global_settings).chain(&cascade).collect::<Vec<_>>();
+    let settings = Settings::resolve(precedence.iter().copied());
+
+    let rows = settings
+        .values()
+        .into_iter()
+        .map(|(name, value)| {
+            let source = precedence
+                .iter()
+                .rev()
+                .find(|s| s.is_set(name))
+                .map(|s| format_path(&s.file, data_dir))
+                .unwrap_or_else(|| "(default)".to_string());
+            [name.to_string(), value, source]
+        })
+        .collect::<Vec<_>>();
+
+    let cells = rows
+        .iter()
+        .map(|row| row.iter().map(|cell| cell.as_str()).collect())
+        .collect();
+    let table = output.format_table(&["Setting", "Value", "Source"], cells, None);
+    output.notify_str(&table);
+}
+
+fn format_path(path: &Path, data_dir: &Path) -> String {
+    path.strip_prefix(data_dir)
+        .unwrap_or(path)
+        .to_string_lossy()
+        .to_string()
+}
+
+fn format_origin(origin: &Origin, data_dir: &Path) -> String {
+    let path = format_path(origin.path, data_dir);
+    match &origin.tag {
+        Some(tag) => format!("{} ({})", path, tag),
+        None => path,
+    }
+}
+
+/// Renders the resolved values in the given `format`, writing them to `file` if
+/// given, or otherwise printing them.
+pub fn export(
+    output: &mut Output,
+    filter_options: &FilterOptions,
+    saucefile: &Saucefile,
+    format: ExportFormat,
+    file: Option<&Path>,
+) {
+    let export = Export {
+        vars: saucefile.vars(filter_options, output),
+        aliases: saucefile.aliases(filter_options, output),
+        functions: saucefile.functions(filter_options, output),
+        path_entries: saucefile.path_entries(filter_options, output),
+    };
+
+    let (content, skipped) = export.render(format);
+    if !skipped.is_empty() {
+        output.notify(&[
+            YELLOW.paint("Skipped "),
+            YELLOW.bold().paint(skipped.join(", ")),
+            YELLOW.paint(", which can't be represented in the given format"),
+        ]);
+    }
+
+    match file {
+        Some(file) => {
+            output.write_file(file, &content).ok();
+        }
+        None => {
+            // `output` adds a trailing newline of its own.
+            output.output(content.trim_end_matches('\n'));
+        }
+    }
+}
+
+/// Explains how `key` resolves through the cascade: every variant of it defined by
+/// each saucefile, which of them takes effect given the current tags, and whether
+/// it's excluded by filters (or kept by `sauce clear`, per `clear-ignore`).
+pub fn which(
+    output: &mut Output,
+    filter_options: &FilterOptions,
+    saucefile: &Saucefile,
+    global_settings: &Settings,
+    key: &str,
+    data_dir: &Path,
+    json: bool,
+) {
+    let settings = saucefile.resolve_settings(global_settings);
+    let clear_ignore = settings
+        .clear_ignore
+        .iter()
+        .flat_map(|i| parse_match_option(Some(i)))
+        .collect::<Vec<_>>();
+
+    let tags = filter_options
+        .as_
+        .iter()
+        .flatten()
+        .map(|tag| tag.as_str())
+        .chain(["default"])
         .collect::<Vec<_>>();
-    let table = output.format_table(header, cells, preset);
+    // Values are selected as though unfiltered, so filtered values can be explained.
+    let unfiltered = FilterOptions {
+        as_: filter_options.as_.clone(),
+        ..Default::default()
+    };
+
+    let mut targets = Vec::new();
+    for target in [
+        Target::EnvVar,
+        Target::Alias,
+        Target::Function,
+        Target::File,
+    ] {
+        let sections = target.sections();
+        if let Some(name) = filter_options.target {
+            if !sections.contains(&name) {
+                continue;
+            }
+        }
+
+        let variants = saucefile.variants(&target, key);
+        if variants.is_empty() {
+            continue;
+        }
+
+        let origins = saucefile.origins(&target, &unfiltered);
+        let selected = origins.get(key).cloned().unwrap_or_default();
+        let is_selected = |variant: &Origin, origin: &Origin| {
+            variant.path == origin.path
+                && variant.tag == origin.tag
+                && (variant.value == TargetValue::Unset) == (origin.value == TargetValue::Unset)
+        };
+
+        let definitions = variants
+            .into_iter()
+            .map(|variant| {
+                let status = match selected.iter().rposition(|o| is_selected(&variant, o)) {
+                    Some(i) if i + 1 == selected.len() => "selected",
+                    Some(_) => "shadowed",
+                    None => "unused",
+                };
+                (variant, status)
+            })
+            .collect::<Vec<_>>();
+
+        let mut excluded_by = Vec::new();
+        if !filter_options.glob_match(sections, key) {
+            excluded_by.push("glob");
+        }
+        if !filter_options.filter_match(sections, key) {
+            excluded_by.push("filter");
+        }
+        if !filter_options.filter_exclude(sections, key) {
+            excluded_by.push("exclusion");
+        }
+        let clear_ignored = !FilterOptions {
+            filter_exclusions: &clear_ignore,
+            ..Default::default()
+        }
+        .filter_exclude(sections, key);
 
+        let value = selected.last().and_then(|o| match o.value {
+            TargetValue::Unset => None,
+            ref value => Some(value.to_string()),
+        });
+        targets.push((sections[0], value, definitions, excluded_by, clear_ignored));
+    }
+
+    if json {
+        let targets = targets
+            .iter()
+            .map(|(target, value, definitions, excluded_by, clear_ignored)| {
+                let definitions = definitions
+                    .iter()
+                    .map(|(variant, status)| {
+                        serde_json::json!({
+                            "saucefile": variant.path,
+                            "tag": variant.tag,
+                            "value": match variant.value {
+                                TargetValue::Unset => None,
+                                ref value => Some(value.to_string()),
+                            },
+                            "unset": variant.value == TargetValue::Unset,
+                            "status": status,
+                        })
+                    })
+                    .collect::<Vec<_>>();
+                serde_json::json!({
+                    "target": target,
+                    "value": value,
+                    "definitions": definitions,
+                    "excluded_by": excluded_by,
+                    "clear_ignore": clear_ignored,
+                })
+            })
+            .collect::<Vec<_>>();
+        let result = serde_json::json!({
+            "key": key,
+            "tags": tags,
+            "targets": targets,
+        });
+        // Printed to stdout (like `export`), so that it can be piped into other tools.
+        output.output(format!("{:#}", result));
+        return;
+    }
+
+    if targets.is_empty() {
+        output.notify(&[RED.paint("No value found for "), YELLOW.paint(key)]);
+        return;
+    }
+
+    output.notify(&[
+        BLUE.bold().paint("Resolving "),
+        YELLOW.paint(key),
+        BLUE.bold().paint(" as "),
+        YELLOW.paint(tags.join(", ")),
+    ]);
+
+    let mut rows = Vec::new();
+    for (target, _, definitions, _, _) in targets.iter() {
+        for (variant, status) in definitions {
+            let value = match variant.value {
+                TargetValue::Unset => "(unset)".to_string(),
+                ref value => value.to_string(),
+            };
+            rows.push(vec![
+                format_path(variant.path, data_dir),
+                target.to_string(),
+                variant.tag.clone().unwrap_or_default(),
+                value,
+                status.to_string(),
+            ]);
+        }
+    }
+    let cells = rows
+        .iter()
+        .map(|row| row.iter().map(|c| c.as_str()).collect())
+        .collect();
+    let table = output.format_table(
+        &["Saucefile", "Target", "Tag", "Value", "Status"],
+        cells,
+        None,
+    );
+    output.notify_str(&table);
+
+    for (target, _, _, excluded_by, clear_ignored) in targets.iter() {
+        if !excluded_by.is_empty() {
+            output.notify(&[
+                YELLOW.paint(format!("{} ({})", key, target)),
+                BLUE.bold().paint(" is excluded by "),
+                YELLOW.paint(excluded_by.join(", ")),
+            ]);
+        }
+        if *clear_ignored {
+            output.notify(&[
+                YELLOW.paint(format!("{} ({})", key, target)),
+                BLUE.bold().paint(" is kept by `sauce clear`, per "),
+                YELLOW.paint("clear-ignore"),
+            ]);
+        }
+    }
+}
+
+pub fn status(output: &mut Output, loaded: &LoadedState) {
+    let mut rows = Vec::new();
+    let mut drifted = false;
+
+    for (var, loaded_digest) in loaded.vars.iter() {
+        // A variable has drifted if it's been changed since sauce set it.
+        let current = std::env::var(var).ok();
+        let status = match &current {
+            Some(current) if &digest(current) == loaded_digest => "loaded",
+            Some(_) => "changed",
+            None => "unset",
+        };
+        drifted |= status != "loaded";
+        rows.push(("env", var.to_string(), current.unwrap_or_default(), status));
+    }
+
+    for path_entries in loaded.path_entries.iter() {
+        let current = std::env::var(&path_entries.var).unwrap_or_default();
+        let present = path_entries
+            .entries
+            .iter()
+            .all(|e| current.split(':').any(|c| c == e));
+        let status = if present { "loaded" } else { "changed" };
+        drifted |= !present;
+
+        let entries = path_entries
+            .entries
+            .iter()
+            .map(|e| format!("{} {}", path_entries.position.name(), e))
+            .collect::<Vec<_>>();
+        rows.push(("path", path_entries.var.clone(), entries.join("\n"), status));
+    }
+
+    for var in loaded.unset.iter() {
+        let status = match std::env::var(var) {
+            Ok(_) => "changed",
+            Err(_) => "loaded",
+        };
+        drifted |= status != "loaded";
+        rows.push(("env", var.to_string(), "(unset)".to_string(), status));
+    }
+
+    let targets = [
+        ("alias", &loaded.aliases),
+        ("function", &loaded.functions),
+        ("file", &loaded.files),
+    ];
+    for (target, keys) in targets {
+        for key in keys.iter() {
+            rows.push((target, key.to_string(), String::new(), "loaded"));
+        }
+    }
+
+    let cells = rows
+        .iter()
+        .map(|(target, name, value, status)| vec![*target, name.as_str(), value.as_str(), *status])
+        .collect::<Vec<_>>();
+    let table = output.format_table(&["Target", "Name", "Value", "Status"], cells, None);
     output.notify_str(&table);
+
+    if drifted {
+        output.notify(&[YELLOW
+            .paint("Some values have changed since they were sauced, run `sauce` to reload them")]);
+    }
 }
 
+#[allow(clippy::too_many_arguments)]
 pub fn execute(
     output: &mut Output,
     shell: &dyn Shell,
@@ -122,34 +755,313 @@ pub fn execute(
     global_settings: &Settings,
     filter_options: &FilterOptions,
     autoload_flag: bool,
+    dir: &Path,
+    loaded: &LoadedState,
 ) -> bool {
     // The `autoload_flag` indicates that the "context" of the execution is happening during
     // an autoload, i.e. `cd`. It's the precondition for whether we need to check the settings to
     // see whether we **actually** should perform the autoload, or exit early.
-    if autoload_flag
-        && !saucefile
-            .settings()
-            .resolve_precedence(global_settings)
-            .autoload
-    {
-        return false;
+    if autoload_flag {
+        let paths = saucefile.paths().collect::<Vec<_>>();
+        let autoload = saucefile.resolve_settings(global_settings).autoload;
+
+        if paths.is_empty() || !autoload {
+            // Having left the tree which loaded the current values, nothing will
+            // replace them, so they're all unloaded.
+            if loaded.is_left(&paths) {
+                unload(output, shell, loaded);
+                output.output(render_keys(vec![LoadedState::VAR], |k| shell.unset_var(k)));
+                output.notify(&[BLUE.bold().paint("Cleared your sauce")]);
+            }
+            return false;
+        }
+    }
+
+    let vars = saucefile.vars(filter_options, output);
+    let path_entries = saucefile.path_entries(filter_options, output);
+    let aliases = saucefile.aliases(filter_options, output);
+    let functions = saucefile.functions(filter_options, output);
+    let files = saucefile.files(filter_options, output);
+    let on_enter = saucefile.hooks("on_enter", filter_options, output);
+    let on_leave = saucefile.hooks("on_leave", filter_options, output);
+
+    let mut state = loaded_state(
+        saucefile,
+        filter_options,
+        dir,
+        &vars,
+        &path_entries,
+        &on_leave,
+    );
+    // A file whose value failed to resolve isn't written, so it mustn't be recorded
+    // either (or clearing would remove whatever file is already there).
+    state
+        .files
+        .retain(|f| files.iter().any(|(k, _)| dir.join(k) == Path::new(f)));
+    let unset = |target| saucefile.unset_keys(&target, filter_options);
+
+    // An autoload replaces the previously loaded values, so anything which is no
+    // longer set is unloaded. Otherwise sauce'ing accumulates values on top of what
+    // was already loaded.
+    if autoload_flag {
+        unload(output, shell, &loaded.without(&state));
     }
 
-    output.output(render_items(saucefile.vars(filter_options), |k, v| {
-        shell.set_var(k, v)
+    // Entries which are about to be re-added are removed first, to avoid duplicating them.
+    output.output(render_loaded_path_entries(
+        loaded
+            .path_entries
+            .iter()
+            .filter(|p| state.path_entries.contains(p)),
+        shell,
+    ));
+
+    output.output(render_items(vars, |k, v| {
+        format!("{};\n{}", shell.save_var(k), shell.set_var(k, v))
     }));
-    output.output(render_items(saucefile.aliases(filter_options), |k, v| {
-        shell.set_alias(k, v)
+    output.output(render_keys(unset(Target::EnvVar), |k| {
+        format!("{};\n{}", shell.save_var(k), shell.unset_var(k))
     }));
-    output.output(render_items(saucefile.functions(filter_options), |k, v| {
-        shell.set_function(k, v)
+
+    output.output(render_path_entries(path_entries, |p| {
+        shell.add_path(p.var, p.position, &p.entries)
+    }));
+
+    output.output(render_items(aliases, |k, v| {
+        format!("{};\n{}", shell.save_alias(k), shell.set_alias(k, v))
+    }));
+    output.output(render_keys(unset(Target::Alias), |k| {
+        format!("{};\n{}", shell.save_alias(k), shell.unset_alias(k))
     }));
-    output.output(render_items(saucefile.files(filter_options), |k, v| {
-        shell.set_file(k, v)
+
+    output.output(render_items(functions, |k, v| {
+        format!("{};\n{}", shell.save_function(k), shell.set_function(k, v))
+    }));
+    output.output(render_keys(unset(Target::Function), |k| {
+        format!("{};\n{}", shell.save_function(k), shell.unset_function(k))
     }));
+
+    let file_mode = saucefile.resolve_settings(global_settings).file_mode;
+    let written = write_files(output, dir, files, file_mode, loaded);
+    state
+        .files
+        .retain(|f| written.iter().any(|w| w == Path::new(f)));
+
+    // Unsetting a file target only stops an ancestor's target from being written. A
+    // file which was already written is removed (restoring whatever it replaced), but
+    // any other file is left alone. An autoload has already unloaded such files.
+    let unset_files = unset(Target::File)
+        .iter()
+        .map(|k| dir.join(k).to_string_lossy().to_string())
+        .filter(|f| !autoload_flag && loaded.files.contains(f))
+        .collect::<Vec<_>>();
+    remove_files(output, unset_files.iter());
+
+    // An autoload only runs the hooks of the saucefiles it newly entered.
+    output.output(render_hooks(
+        shell,
+        "on_enter",
+        on_enter
+            .iter()
+            .filter(|(path, _)| !autoload_flag || !loaded.paths.contains(path))
+            .map(|(_, hook)| hook),
+    ));
+
+    let state = if autoload_flag {
+        state
+    } else {
+        let mut state = loaded.merge(&state);
+        state.files.retain(|f| !unset_files.contains(f));
+        state
+    };
+    output.output(render_loaded_state(shell, loaded, &state));
     true
 }
 
+/// Reverts the values recorded in `loaded`.
+fn unload(output: &mut Output, shell: &dyn Shell, loaded: &LoadedState) {
+    fn keys<'k>(keys: impl IntoIterator<Item = &'k String>) -> Vec<&'k str> {
+        keys.into_iter().map(|k| k.as_str()).collect()
+    }
+
+    output.output(render_keys(keys(loaded.vars.keys()), |k| {
+        shell.restore_var(k)
+    }));
+    output.output(render_keys(keys(&loaded.unset), |k| shell.restore_var(k)));
+    output.output(render_keys(keys(&loaded.aliases), |k| {
+        shell.restore_alias(k)
+    }));
+    output.output(render_keys(keys(&loaded.functions), |k| {
+        shell.restore_function(k)
+    }));
+    remove_files(output, loaded.files.iter());
+    output.output(render_loaded_path_entries(
+        loaded.path_entries.iter(),
+        shell,
+    ));
+    output.output(render_hooks(
+        shell,
+        "on_leave",
+        loaded.on_leave.iter().rev().map(|h| &h.hook),
+    ));
+}
+
+/// The path to which a pre-existing file is moved while a `[file]` target replaces it.
+fn backup_path(file: &Path) -> PathBuf {
+    let mut name = file.file_name().unwrap_or_default().to_os_string();
+    name.push(".sauce-backup");
+    file.with_file_name(name)
+}
+
+/// Writes the `[file]` targets `files`, relative to `dir`, returning the paths of
+/// those which were written.
+///
+/// A pre-existing file (which sauce didn't itself write) is first backed up, so
+/// that it can be restored once the target is cleared. If a backup already exists
+/// (say, left behind by a shell which never cleared), the target isn't written, as
+/// the file couldn't then be backed up without losing one or the other.
+fn write_files(
+    output: &mut Output,
+    dir: &Path,
+    files: Vec<(&str, String)>,
+    mode: Option<u32>,
+    loaded: &LoadedState,
+) -> Vec<PathBuf> {
+    let mut result = Vec::new();
+    for (name, content) in files {
+        let file = dir.join(name);
+        let written = loaded.files.iter().any(|f| Path::new(f) == file);
+        let backup = backup_path(&file);
+        if file.exists() && !written {
+            if backup.exists() {
+                output.notify_error(
+                    ErrorCode::WriteError,
+                    &[
+                        RED.bold().paint("Not writing "),
+                        YELLOW.paint(file.to_string_lossy()),
+                        RED.bold().paint(", as its backup "),
+                        YELLOW.paint(backup.to_string_lossy()),
+                        RED.bold().paint(" already exists"),
+                    ],
+                );
+                continue;
+            }
+            if output.move_file(&file, &backup, false).is_err() {
+                continue;
+            }
+        }
+        if output.write_target_file(&file, &content, mode).is_ok() {
+            result.push(file);
+        }
+    }
+    result
+}
+
+/// Removes the `[file]` targets `files`, restoring any files they replaced.
+fn remove_files<P: AsRef<Path>>(output: &mut Output, files: impl IntoIterator<Item = P>) {
+    for file in files {
+        let file = file.as_ref();
+        if file.exists() {
+            output.remove_file(file).ok();
+        }
+
+        let backup = backup_path(file);
+        if backup.exists() {
+            output.move_file(&backup, file, false).ok();
+        }
+    }
+}
+
+/// The state recorded for the values of `saucefile` which pass `filter_options`,
+/// whose `[file]` targets are relative to `dir`.
+fn loaded_state(
+    saucefile: &Saucefile,
+    filter_options: &FilterOptions,
+    dir: &Path,
+    vars: &[(&str, String)],
+    path_entries: &[PathEntries],
+    on_leave: &[(&PathBuf, String)],
+) -> LoadedState {
+    let keys = |target| -> Vec<String> {
+        saucefile
+            .keys(&target, filter_options)
+            .into_iter()
+            .map(|k| k.to_string())
+            .collect()
+    };
+
+    let unset_files = saucefile.unset_keys(&Target::File, filter_options);
+    LoadedState {
+        paths: saucefile.paths().cloned().collect(),
+        tags: filter_options.as_.clone().unwrap_or_default(),
+        vars: vars
+            .iter()
+            .map(|(k, v)| (k.to_string(), digest(v)))
+            .collect(),
+        unset: saucefile
+            .unset_keys(&Target::EnvVar, filter_options)
+            .into_iter()
+            .map(|k| k.to_string())
+            .collect(),
+        aliases: keys(Target::Alias),
+        functions: keys(Target::Function),
+        files: keys(Target::File)
+            .into_iter()
+            .filter(|k| !unset_files.contains(&k.as_str()))
+            .map(|k| dir.join(k).to_string_lossy().to_string())
+            .collect(),
+        path_entries: path_entries
+            .iter()
+            .filter(|p| !p.entries.is_empty())
+            .map(|p| LoadedPathEntries {
+                var: p.var.to_string(),
+                position: p.position,
+                entries: p.entries.clone(),
+            })
+            .collect(),
+        on_leave: on_leave
+            .iter()
+            .map(|(path, hook)| LoadedHook {
+                path: path.to_path_buf(),
+                hook: hook.clone(),
+            })
+            .collect(),
+    }
+}
+
+/// Renders the `[hooks]` commands `hooks`, which are named `name`.
+fn render_hooks<'h>(
+    shell: &dyn Shell,
+    name: &str,
+    hooks: impl IntoIterator<Item = &'h String>,
+) -> String {
+    hooks
+        .into_iter()
+        .map(|hook| shell.hook(name, hook) + ";\n")
+        .collect()
+}
+
+fn render_loaded_state(shell: &dyn Shell, previous: &LoadedState, state: &LoadedState) -> String {
+    if state == previous {
+        String::new()
+    } else if state.is_empty() {
+        render_keys(vec![LoadedState::VAR], |k| shell.unset_var(k))
+    } else {
+        render_items(vec![(LoadedState::VAR, state.to_string())], |k, v| {
+            shell.set_var(k, v)
+        })
+    }
+}
+
+fn render_loaded_path_entries<'a>(
+    path_entries: impl Iterator<Item = &'a LoadedPathEntries>,
+    shell: &dyn Shell,
+) -> String {
+    path_entries
+        .map(|p| shell.remove_path(&p.var, p.position, &p.entries) + ";\n")
+        .collect()
+}
+
 fn render_items<F>(items: Vec<(&str, String)>, mut format_row: F) -> String
 where
     F: FnMut(&str, &str) -> String,
@@ -164,6 +1076,24 @@ where
         .collect()
 }
 
+fn render_keys<F>(keys: Vec<&str>, mut format_row: F) -> String
+where
+    F: FnMut(&str) -> String,
+{
+    keys.iter().map(|k| format_row(k) + ";\n").collect()
+}
+
+fn render_path_entries<F>(path_entries: Vec<PathEntries>, mut format_row: F) -> String
+where
+    F: FnMut(&PathEntries) -> String,
+{
+    path_entries
+        .iter()
+        .filter(|p| !p.entries.is_empty())
+        .map(|p| format_row(p) + ";\n")
+        .collect()
+}
+
 #[cfg(test)]
 mod tests {
     use crate::toml::{ensure_section, value_from_string};
@@ -183,9 +1113,9 @@ mod tests {
             let (out, err, mut output) = setup();
 
             let shell = TestShell {};
-            edit(&mut output, &shell, &Path::new("foo/bar"));
+            edit(&mut output, &shell, Path::new("foo/bar"));
 
-            assert_eq!(out.value(), "edit 'foo/bar'\n");
+            assert_eq!(out.value(), "edit foo/bar\n");
             assert_eq!(err.value(), "Opening foo/bar\n");
         }
     }
@@ -237,37 +1167,137 @@ mod tests {
             let section = ensure_section(&mut saucefile.document, "function");
             section["fn"] = value_from_string("fnvalue");
 
+            let section = ensure_section(&mut saucefile.document, "path");
+            section["append"] = value_from_string("/bin");
+
             clear(
                 &mut output,
                 &shell,
-                &mut saucefile,
+                &saucefile,
                 &Settings::default(),
                 &FilterOptions::default(),
+                Path::new(""),
+                &LoadedState::default(),
             );
 
-            assert_eq!(out.value(), "unset var;\n\nunalias alias;\n\nunset fn;\n\n");
+            assert_eq!(
+                out.value(),
+                "restore var;\n\nrestore alias alias;\n\nrestore function fn;\n\nunappend PATH=/bin;\n\n"
+            );
             assert_eq!(err.value(), "Cleared your sauce\n");
         }
-    }
-
-    mod show {
-        use super::super::*;
-        use super::*;
-        use pretty_assertions::assert_eq;
 
         #[test]
-        fn it_shows_env_vars() {
-            let (out, err, mut output) = setup();
-            let mut saucefile = Saucefile::default();
+        fn it_runs_leave_hooks() {
+            let shell = TestShell {};
+            let (out, _, mut output) = setup();
+            let path = PathBuf::from("/sauce/a.toml");
+            let mut saucefile = Saucefile {
+                path: Some(path.clone()),
+                ..Default::default()
+            };
 
-            let section = ensure_section(&mut saucefile.document, "environment");
-            section["var"] = value_from_string("varvalue");
+            let section = ensure_section(&mut saucefile.document, "hooks");
+            section["on_enter"] = value_from_string("echo hi");
+            section["on_leave"] = value_from_string("echo changed");
 
-            show(
+            // The recorded hooks run, and only those of saucefiles in the cascade.
+            clear(
+                &mut output,
+                &shell,
+                &saucefile,
+                &Settings::default(),
+                &FilterOptions::default(),
+                Path::new(""),
+                &LoadedState {
+                    on_leave: vec![
+                        LoadedHook {
+                            path: PathBuf::from("/elsewhere/b.toml"),
+                            hook: "echo elsewhere".to_string(),
+                        },
+                        LoadedHook {
+                            path,
+                            hook: "echo bye".to_string(),
+                        },
+                    ],
+                    ..Default::default()
+                },
+            );
+
+            assert_eq!(
+                out.value(),
+                indoc!(
+                    r#"
+                    echo bye;
+
+                    export SAUCE_LOADED=on_leave = [{ path = "/elsewhere/b.toml", hook = "echo elsewhere" }];
+
+                    "#
+                )
+            );
+        }
+
+        #[test]
+        fn it_only_removes_files_which_sauce_wrote() {
+            let shell = TestShell {};
+            let (_, _, mut output) = setup();
+            let dir = std::env::temp_dir().join(format!("sauce-clear-{}", std::process::id()));
+            let subdir = dir.join("sub");
+            std::fs::create_dir_all(&subdir).unwrap();
+            let mine = subdir.join(".env");
+            let written = dir.join("written");
+            std::fs::write(&mine, "mine").unwrap();
+            std::fs::write(&written, "written").unwrap();
+            std::fs::write(backup_path(&written), "original").unwrap();
+
+            let mut saucefile = Saucefile::default();
+            let section = ensure_section(&mut saucefile.document, "file");
+            section[".env"] = value_from_string("A=1");
+            section["written"] = value_from_string("B=2");
+
+            // Cleared from a subdirectory, which has a `.env` that sauce never wrote.
+            clear(
+                &mut output,
+                &shell,
+                &saucefile,
+                &Settings::default(),
+                &FilterOptions::default(),
+                &subdir,
+                &LoadedState {
+                    files: vec![written.to_string_lossy().to_string()],
+                    ..Default::default()
+                },
+            );
+
+            assert_eq!(std::fs::read_to_string(&mine).unwrap(), "mine");
+            assert_eq!(std::fs::read_to_string(&written).unwrap(), "original");
+            assert_eq!(backup_path(&written).exists(), false);
+
+            std::fs::remove_dir_all(&dir).ok();
+        }
+    }
+
+    mod show {
+        use super::super::*;
+        use super::*;
+        use pretty_assertions::assert_eq;
+        use std::path::PathBuf;
+        use toml_edit::Document;
+
+        #[test]
+        fn it_shows_env_vars() {
+            let (out, err, mut output) = setup();
+            let mut saucefile = Saucefile::default();
+
+            let section = ensure_section(&mut saucefile.document, "environment");
+            section["var"] = value_from_string("varvalue");
+
+            show(
                 &mut output,
                 &FilterOptions::default(),
                 Target::EnvVar,
-                &mut saucefile,
+                &saucefile,
+                None,
             );
 
             assert_eq!(out.value(), "");
@@ -298,6 +1328,7 @@ mod tests {
                 &FilterOptions::default(),
                 Target::Alias,
                 &saucefile,
+                None,
             );
 
             assert_eq!(out.value(), "");
@@ -315,6 +1346,44 @@ mod tests {
             );
         }
 
+        #[test]
+        fn it_shows_origins() {
+            let (_, err, mut output) = setup();
+            let mut saucefile = Saucefile::default();
+
+            let mut ancestor = Document::new();
+            let section = ensure_section(&mut ancestor, "environment");
+            section["var"] = value_from_string("outer");
+            saucefile
+                .ancestors
+                .push((PathBuf::from("/data/a.toml"), ancestor));
+
+            let section = ensure_section(&mut saucefile.document, "environment");
+            section["var"] = value_from_string("inner");
+            saucefile.path = Some(PathBuf::from("/data/a/b.toml"));
+
+            show(
+                &mut output,
+                &FilterOptions::default(),
+                Target::EnvVar,
+                &saucefile,
+                Some(Path::new("/data")),
+            );
+
+            assert_eq!(
+                err.value(),
+                indoc!(
+                    "
+                    ┌──────────┬───────┬──────────┬───────────────────┐
+                    │ Variable │ Value │ Origin   │ Shadowed          │
+                    ╞══════════╪═══════╪══════════╪═══════════════════╡
+                    │ var      │ inner │ a/b.toml │ outer from a.toml │
+                    └──────────┴───────┴──────────┴───────────────────┘
+                    "
+                )
+            );
+        }
+
         #[test]
         fn it_shows_functions() {
             let (out, err, mut output) = setup();
@@ -328,6 +1397,7 @@ mod tests {
                 &FilterOptions::default(),
                 Target::Function,
                 &saucefile,
+                None,
             );
 
             assert_eq!(out.value(), "");
@@ -347,10 +1417,394 @@ mod tests {
         }
     }
 
+    mod run {
+        use super::super::*;
+        use super::*;
+        use pretty_assertions::assert_eq;
+
+        #[test]
+        fn it_shows_the_task_script() {
+            let (out, err, mut output) = setup();
+            output.set_show(true);
+            let mut saucefile = Saucefile::default();
+
+            let section = ensure_section(&mut saucefile.document, "environment");
+            section["var"] = value_from_string("varvalue");
+            let section = ensure_section(&mut saucefile.document, "function");
+            section["greet"] = value_from_string("echo hi");
+
+            run(
+                &mut output,
+                &TestShell {},
+                &saucefile,
+                &FilterOptions::default(),
+                "greet",
+                &["a b".to_string()],
+            );
+
+            assert_eq!(out.value(), "");
+            assert_eq!(
+                err.value(),
+                "export var=varvalue\nfunction greet=echo hi\ngreet 'a b'\n"
+            );
+        }
+
+        #[test]
+        fn it_writes_nushell_source() {
+            let (_, err, mut output) = setup();
+            output.set_show(true);
+            let mut saucefile = Saucefile::default();
+
+            let section = ensure_section(&mut saucefile.document, "environment");
+            section["var"] = value_from_string("varvalue");
+            let section = ensure_section(&mut saucefile.document, "function");
+            section["greet"] = value_from_string("print hi");
+
+            run(
+                &mut output,
+                &crate::shell::Nushell {},
+                &saucefile,
+                &FilterOptions::default(),
+                "greet",
+                &["a b".to_string()],
+            );
+
+            assert_eq!(
+                err.value(),
+                "$env.var = r#'varvalue'#\ndef --wrapped greet [...args] {\n  print hi\n}\ngreet r#'a b'#\n"
+            );
+        }
+
+        #[test]
+        fn it_errors_on_missing_functions() {
+            let (_, err, mut output) = setup();
+            let saucefile = Saucefile::default();
+
+            run(
+                &mut output,
+                &TestShell {},
+                &saucefile,
+                &FilterOptions::default(),
+                "greet",
+                &[],
+            );
+
+            assert_eq!(err.value(), "No function found for greet\n");
+            assert_eq!(output.error_code(), Some(4));
+        }
+    }
+
+    mod list_functions {
+        use super::super::*;
+        use super::*;
+        use pretty_assertions::assert_eq;
+        use std::path::PathBuf;
+        use toml_edit::Document;
+
+        #[test]
+        fn it_lists_function_names() {
+            let (out, err, mut output) = setup();
+            let mut saucefile = Saucefile::default();
+
+            let section = ensure_section(&mut saucefile.document, "function");
+            section["build"] = value_from_string("cargo build");
+            section["test"] = value_from_string("cargo test");
+
+            list_functions(&mut output, &saucefile, &FilterOptions::default());
+
+            assert_eq!(out.value(), "build\ntest\n");
+            assert_eq!(err.value(), "");
+        }
+
+        #[test]
+        fn it_leaves_out_unset_functions() {
+            let (out, err, mut output) = setup();
+            let mut saucefile = Saucefile::default();
+
+            let ancestor = "[function]\ndeploy = \"./deploy\"\nbuild = \"cargo build\""
+                .parse::<Document>()
+                .expect("invalid doc");
+            saucefile
+                .ancestors
+                .push((PathBuf::from("work.toml"), ancestor));
+            let section = ensure_section(&mut saucefile.document, "function");
+            section["deploy"] = value_from_string("{unset = true}");
+
+            list_functions(&mut output, &saucefile, &FilterOptions::default());
+
+            assert_eq!(out.value(), "build\n");
+            assert_eq!(err.value(), "");
+        }
+
+        #[test]
+        fn it_notes_when_there_are_none() {
+            let (out, err, mut output) = setup();
+
+            list_functions(
+                &mut output,
+                &Saucefile::default(),
+                &FilterOptions::default(),
+            );
+
+            assert_eq!(out.value(), "");
+            assert_eq!(err.value(), "No functions found\n");
+        }
+    }
+
+    mod child_environment {
+        use super::super::*;
+        use pretty_assertions::assert_eq;
+
+        #[test]
+        fn it_applies_vars_unsets_and_path_entries() {
+            let vars = vec![("FOO", "foo".to_string())];
+            let path_entries = vec![
+                PathEntries {
+                    origin: Path::new("a.toml"),
+                    var: "PATH",
+                    position: PathPosition::Prepend,
+                    entries: vec!["/a/bin".to_string()],
+                },
+                PathEntries {
+                    origin: Path::new("a.toml"),
+                    var: "PYTHONPATH",
+                    position: PathPosition::Append,
+                    entries: vec!["/a/src".to_string()],
+                },
+            ];
+            let current = |var: &str| (var == "PATH").then(|| "/usr/bin".to_string());
+
+            let result = child_environment(&vars, &["BAR"], &path_entries, current);
+            assert_eq!(
+                result.into_iter().collect::<Vec<_>>(),
+                vec![
+                    ("BAR".to_string(), None),
+                    ("FOO".to_string(), Some("foo".to_string())),
+                    ("PATH".to_string(), Some("/a/bin:/usr/bin".to_string())),
+                    ("PYTHONPATH".to_string(), Some("/a/src".to_string())),
+                ]
+            );
+        }
+    }
+
+    mod show_settings {
+        use super::super::*;
+        use super::*;
+        use pretty_assertions::assert_eq;
+        use std::path::PathBuf;
+        use toml_edit::Document;
+
+        #[test]
+        fn it_shows_where_each_setting_came_from() {
+            let (_, err, mut output) = setup();
+            let mut saucefile = Saucefile::default();
+            saucefile.ancestors.push((
+                PathBuf::from("/data/work.toml"),
+                "[settings]\nautoload = true\nautoload-args = \"--as ci\""
+                    .parse::<Document>()
+                    .expect("invalid doc"),
+            ));
+            let global = Settings {
+                file: PathBuf::from("/config/sauce.toml"),
+                autoload: Some(false),
+                file_mode: Some(0o600),
+                ..Default::default()
+            };
+
+            show_settings(&mut output, &saucefile, &global, Path::new("/data"));
+
+            let table = err.value();
+            let rows = table
+                .lines()
+                .map(|line| {
+                    line.split('│')
+                        .map(|cell| cell.trim())
+                        .filter(|cell| !cell.is_empty())
+                        .collect::<Vec<_>>()
+                })
+                .collect::<Vec<_>>();
+            assert_eq!(rows.contains(&vec!["autoload", "true", "work.toml"]), true);
+            assert_eq!(
+                rows.contains(&vec!["file-mode", "\"600\"", "/config/sauce.toml"]),
+                true
+            );
+            assert_eq!(
+                rows.contains(&vec!["autoload-hook", "false", "(default)"]),
+                true
+            );
+            // Only read from the global config, so the saucefile's value is ignored.
+            assert_eq!(
+                rows.contains(&vec!["autoload-args", "\"\"", "(default)"]),
+                true
+            );
+        }
+    }
+
+    mod which {
+        use super::super::*;
+        use super::*;
+        use pretty_assertions::assert_eq;
+        use std::path::PathBuf;
+        use toml_edit::Document;
+
+        #[test]
+        fn it_explains_each_variant() {
+            let (out, err, mut output) = setup();
+            let mut saucefile = Saucefile::default();
+
+            let ancestor = "[env]\nFOO = {default = \"foo\", prod = \"prod-foo\"}"
+                .parse::<Document>()
+                .expect("invalid doc");
+            saucefile
+                .ancestors
+                .push((PathBuf::from("/data/a.toml"), ancestor));
+
+            saucefile.document = "[unset]\nenv = {ci = [\"FOO\"]}"
+                .parse::<Document>()
+                .expect("invalid doc");
+            saucefile.path = Some(PathBuf::from("/data/a/b.toml"));
+
+            let globs = [(None, "BAR")];
+            let filter_options = FilterOptions {
+                as_: Some(vec!["prod".to_string()]),
+                globs: &globs,
+                ..Default::default()
+            };
+            which(
+                &mut output,
+                &filter_options,
+                &saucefile,
+                &Settings::default(),
+                "FOO",
+                Path::new("/data"),
+                false,
+            );
+
+            assert_eq!(out.value(), "");
+            assert_eq!(
+                err.value(),
+                indoc!(
+                    "
+                    Resolving FOO as prod, default
+                    ┌───────────┬────────┬─────────┬──────────┬──────────┐
+                    │ Saucefile │ Target │ Tag     │ Value    │ Status   │
+                    ╞═══════════╪════════╪═════════╪══════════╪══════════╡
+                    │ a.toml    │ env    │ default │ foo      │ unused   │
+                    │ a.toml    │ env    │ prod    │ prod-foo │ selected │
+                    │ a/b.toml  │ env    │ ci      │ (unset)  │ unused   │
+                    └───────────┴────────┴─────────┴──────────┴──────────┘
+                    FOO (env) is excluded by glob
+                    "
+                )
+            );
+        }
+
+        #[test]
+        fn it_notes_filters_and_exclusions() {
+            let (_, err, mut output) = setup();
+            let saucefile = Saucefile {
+                document: "[env]\nFOO = \"foo\"".parse::<Document>().unwrap(),
+                ..Default::default()
+            };
+
+            let filters = [(None, "BAR")];
+            let filter_exclusions = [(Some("env"), "FOO")];
+            let filter_options = FilterOptions {
+                filters: &filters,
+                filter_exclusions: &filter_exclusions,
+                ..Default::default()
+            };
+            which(
+                &mut output,
+                &filter_options,
+                &saucefile,
+                &Settings::default(),
+                "FOO",
+                Path::new("/data"),
+                false,
+            );
+
+            assert_eq!(
+                err.value()
+                    .ends_with("FOO (env) is excluded by filter, exclusion\n"),
+                true
+            );
+        }
+
+        #[test]
+        fn it_writes_json_to_stdout() {
+            let (out, err, mut output) = setup();
+            let saucefile = Saucefile {
+                document: "[env]\nFOO = \"foo\"".parse::<Document>().unwrap(),
+                ..Default::default()
+            };
+
+            which(
+                &mut output,
+                &FilterOptions::default(),
+                &saucefile,
+                &Settings::default(),
+                "FOO",
+                Path::new("/data"),
+                true,
+            );
+
+            let result = serde_json::from_str::<serde_json::Value>(&out.value()).unwrap();
+            assert_eq!(result["key"], "FOO");
+            assert_eq!(result["targets"][0]["value"], "foo");
+            assert_eq!(err.value(), "");
+        }
+    }
+
+    mod status {
+        use super::super::*;
+        use super::*;
+        use indexmap::IndexMap;
+        use pretty_assertions::assert_eq;
+
+        #[test]
+        fn it_flags_drifted_values() {
+            std::env::set_var("SAUCE_STATUS_LOADED", "a");
+            std::env::set_var("SAUCE_STATUS_CHANGED", "c");
+
+            let (out, err, mut output) = setup();
+            let loaded = LoadedState {
+                vars: IndexMap::from([
+                    ("SAUCE_STATUS_LOADED".to_string(), digest("a")),
+                    ("SAUCE_STATUS_CHANGED".to_string(), digest("b")),
+                ]),
+                aliases: vec!["alias".to_string()],
+                ..Default::default()
+            };
+
+            status(&mut output, &loaded);
+
+            assert_eq!(out.value(), "");
+            assert_eq!(
+                err.value(),
+                indoc!(
+                    "
+                    ┌────────┬──────────────────────┬───────┬─────────┐
+                    │ Target │ Name                 │ Value │ Status  │
+                    ╞════════╪══════════════════════╪═══════╪═════════╡
+                    │ env    │ SAUCE_STATUS_LOADED  │ a     │ loaded  │
+                    │ env    │ SAUCE_STATUS_CHANGED │ c     │ changed │
+                    │ alias  │ alias                │       │ loaded  │
+                    └────────┴──────────────────────┴───────┴─────────┘
+                    Some values have changed since they were sauced, run `sauce` to reload them
+                    "
+                )
+            );
+        }
+    }
+
     mod execute {
         use super::super::*;
         use super::*;
+        use crate::value::PathPosition;
+        use indexmap::IndexMap;
         use pretty_assertions::assert_eq;
+        use std::path::PathBuf;
 
         #[test]
         fn it_executes() {
@@ -367,6 +1821,9 @@ mod tests {
             let section = ensure_section(&mut saucefile.document, "function");
             section["fn"] = value_from_string("fnvalue");
 
+            let section = ensure_section(&mut saucefile.document, "path");
+            section["prepend"] = value_from_string("/bin");
+
             execute(
                 &mut output,
                 &shell,
@@ -374,14 +1831,163 @@ mod tests {
                 &Settings::default(),
                 &FilterOptions::default(),
                 false,
+                Path::new(""),
+                &LoadedState::default(),
             );
 
             assert_eq!(
                 out.value(),
-                "export var=varvalue;\n\nalias alias=aliasvalue;\n\nfunction fn=fnvalue;\n\n"
+                indoc!(
+                    r#"
+                    save var;
+                    export var=varvalue;
+
+                    prepend PATH=/bin;
+
+                    save alias alias;
+                    alias alias=aliasvalue;
+
+                    save function fn;
+                    function fn=fnvalue;
+
+                    export SAUCE_LOADED=vars = [{ name = "var", digest = "5ed9ffc36bc02b6283caaa99f5c8c113" }]
+                    aliases = ["alias"]
+                    functions = ["fn"]
+                    path = [{ var = "PATH", position = "prepend", entries = ["/bin"] }];
+
+                    "#
+                )
             );
         }
 
+        #[test]
+        fn it_unsets_values() {
+            let shell = TestShell {};
+            let (out, _, mut output) = setup();
+            let mut saucefile = Saucefile::default();
+
+            let section = ensure_section(&mut saucefile.document, "environment");
+            section["var"] = value_from_string("{unset = true}");
+
+            let section = ensure_section(&mut saucefile.document, "unset");
+            section["alias"] = value_from_string("['alias']");
+
+            execute(
+                &mut output,
+                &shell,
+                &saucefile,
+                &Settings::default(),
+                &FilterOptions::default(),
+                false,
+                Path::new(""),
+                &LoadedState::default(),
+            );
+
+            assert_eq!(
+                out.value(),
+                indoc!(
+                    r#"
+                    save var;
+                    unset var;
+
+                    save alias alias;
+                    unalias alias;
+
+                    export SAUCE_LOADED=unset = ["var"]
+                    aliases = ["alias"];
+
+                    "#
+                )
+            );
+        }
+
+        #[test]
+        fn it_unloads_values_which_are_no_longer_set_on_autoload() {
+            let shell = TestShell {};
+            let (out, _, mut output) = setup();
+            let mut saucefile = Saucefile {
+                path: Some(PathBuf::from("/b.toml")),
+                ..Default::default()
+            };
+
+            let section = ensure_section(&mut saucefile.document, "environment");
+            section["var"] = value_from_string("varvalue");
+
+            let loaded = LoadedState {
+                paths: vec![PathBuf::from("/a.toml")],
+                vars: IndexMap::from([
+                    ("var".to_string(), digest("varvalue")),
+                    ("stale".to_string(), digest("stalevalue")),
+                ]),
+                path_entries: vec![LoadedPathEntries {
+                    var: "PATH".to_string(),
+                    position: PathPosition::Prepend,
+                    entries: vec!["/a/bin".to_string()],
+                }],
+                ..Default::default()
+            };
+
+            let settings = Settings {
+                autoload: Some(true),
+                ..Default::default()
+            };
+            execute(
+                &mut output,
+                &shell,
+                &saucefile,
+                &settings,
+                &FilterOptions::default(),
+                true,
+                Path::new(""),
+                &loaded,
+            );
+
+            assert_eq!(
+                out.value(),
+                indoc!(
+                    r#"
+                    restore stale;
+
+                    unprepend PATH=/a/bin;
+
+                    save var;
+                    export var=varvalue;
+
+                    export SAUCE_LOADED=paths = ["/b.toml"]
+                    vars = [{ name = "var", digest = "5ed9ffc36bc02b6283caaa99f5c8c113" }];
+
+                    "#
+                )
+            );
+        }
+
+        #[test]
+        fn it_clears_when_autoloading_outside_of_the_loaded_tree() {
+            let shell = TestShell {};
+            let (out, err, mut output) = setup();
+
+            let loaded = LoadedState {
+                paths: vec![PathBuf::from("/a.toml")],
+                vars: IndexMap::from([("var".to_string(), digest("varvalue"))]),
+                ..Default::default()
+            };
+
+            let sauced = execute(
+                &mut output,
+                &shell,
+                &Saucefile::default(),
+                &Settings::default(),
+                &FilterOptions::default(),
+                true,
+                Path::new(""),
+                &loaded,
+            );
+
+            assert_eq!(sauced, false);
+            assert_eq!(out.value(), "restore var;\n\nunset SAUCE_LOADED;\n\n");
+            assert_eq!(err.value(), "Cleared your sauce\n");
+        }
+
         #[test]
         fn it_doesnt_execute_with_autoload_flag_and_its_disabled() {
             let shell = TestShell {};
@@ -394,10 +2000,276 @@ mod tests {
                 &Settings::default(),
                 &FilterOptions::default(),
                 true,
+                Path::new(""),
+                &LoadedState::default(),
             );
 
             assert_eq!(out.value(), "");
             assert_eq!(err.value(), "");
         }
     }
+
+    mod hooks {
+        use super::super::*;
+        use super::*;
+        use pretty_assertions::assert_eq;
+
+        fn saucefile() -> Saucefile {
+            let mut saucefile = Saucefile {
+                path: Some(PathBuf::from("/sauce/a.toml")),
+                ..Default::default()
+            };
+            let section = ensure_section(&mut saucefile.document, "hooks");
+            section["on_enter"] = value_from_string("echo ${GREETING}");
+            section["on_leave"] =
+                value_from_string(r#"{ default = "echo bye", prod = "echo prod" }"#);
+
+            let section = ensure_section(&mut saucefile.document, "environment");
+            section["GREETING"] = value_from_string("hi");
+            saucefile
+        }
+
+        #[test]
+        fn it_runs_enter_hooks_after_the_values() {
+            let shell = TestShell {};
+            let (out, _, mut output) = setup();
+
+            execute(
+                &mut output,
+                &shell,
+                &saucefile(),
+                &Settings::default(),
+                &FilterOptions {
+                    as_: Some(vec!["prod".to_string()]),
+                    ..Default::default()
+                },
+                false,
+                Path::new(""),
+                &LoadedState::default(),
+            );
+
+            assert_eq!(
+                out.value(),
+                indoc!(
+                    r#"
+                    save GREETING;
+                    export GREETING=hi;
+
+                    echo ${GREETING};
+
+                    export SAUCE_LOADED=paths = ["/sauce/a.toml"]
+                    tags = ["prod"]
+                    vars = [{ name = "GREETING", digest = "8f434346648f6b96df89dda901c5176b" }]
+                    on_leave = [{ path = "/sauce/a.toml", hook = "echo prod" }];
+
+                    "#
+                )
+            );
+        }
+
+        #[test]
+        fn it_skips_hooks_when_filtered() {
+            let (_, _, mut output) = setup();
+            let saucefile = saucefile();
+            let hooks = saucefile.hooks(
+                "on_enter",
+                &FilterOptions {
+                    target: Some("env"),
+                    ..Default::default()
+                },
+                &mut output,
+            );
+            assert_eq!(hooks, vec![]);
+        }
+
+        #[test]
+        fn it_runs_leave_hooks_when_unloading() {
+            let shell = TestShell {};
+            let (out, _, mut output) = setup();
+
+            unload(
+                &mut output,
+                &shell,
+                &LoadedState {
+                    on_leave: vec![
+                        LoadedHook {
+                            path: PathBuf::from("/sauce.toml"),
+                            hook: "echo bye".to_string(),
+                        },
+                        LoadedHook {
+                            path: PathBuf::from("/sauce/a.toml"),
+                            hook: "echo bye".to_string(),
+                        },
+                    ],
+                    ..Default::default()
+                },
+            );
+
+            assert_eq!(out.value(), "echo bye;\necho bye;\n\n");
+        }
+    }
+
+    mod files {
+        use super::super::*;
+        use super::*;
+        use pretty_assertions::assert_eq;
+
+        #[test]
+        fn it_backs_up_and_restores_existing_files() {
+            let (_, _, mut output) = setup();
+            let dir = std::env::temp_dir().join(format!("sauce-files-{}", std::process::id()));
+            std::fs::create_dir_all(&dir).unwrap();
+            let file = dir.join("nested/.env");
+            std::fs::create_dir_all(file.parent().unwrap()).unwrap();
+            std::fs::write(&file, "original").unwrap();
+
+            let files = vec![("nested/.env", "A=1".to_string())];
+            write_files(
+                &mut output,
+                &dir,
+                files,
+                Some(0o600),
+                &LoadedState::default(),
+            );
+            assert_eq!(std::fs::read_to_string(&file).unwrap(), "A=1");
+            assert_eq!(
+                std::fs::read_to_string(backup_path(&file)).unwrap(),
+                "original"
+            );
+
+            #[cfg(unix)]
+            {
+                use std::os::unix::fs::PermissionsExt;
+                let mode = std::fs::metadata(&file).unwrap().permissions().mode();
+                assert_eq!(mode & 0o777, 0o600);
+            }
+
+            remove_files(&mut output, [&file]);
+            assert_eq!(std::fs::read_to_string(&file).unwrap(), "original");
+            assert_eq!(backup_path(&file).exists(), false);
+
+            std::fs::remove_dir_all(&dir).ok();
+        }
+
+        #[test]
+        fn it_leaves_unset_files_alone_unless_sauce_wrote_them() {
+            let shell = TestShell {};
+            let (_, _, mut output) = setup();
+            let dir = std::env::temp_dir().join(format!("sauce-unset-{}", std::process::id()));
+            std::fs::create_dir_all(&dir).unwrap();
+            let mine = dir.join(".env");
+            let written = dir.join("written");
+            std::fs::write(&mine, "mine").unwrap();
+            std::fs::write(&written, "written").unwrap();
+            std::fs::write(backup_path(&written), "original").unwrap();
+
+            let mut saucefile = Saucefile::default();
+            let section = ensure_section(&mut saucefile.document, "file");
+            section[".env"] = value_from_string("{unset = true}");
+            section["written"] = value_from_string("{unset = true}");
+
+            let loaded = LoadedState {
+                files: vec![written.to_string_lossy().to_string()],
+                ..Default::default()
+            };
+            execute(
+                &mut output,
+                &shell,
+                &saucefile,
+                &Settings::default(),
+                &FilterOptions::default(),
+                false,
+                &dir,
+                &loaded,
+            );
+
+            assert_eq!(std::fs::read_to_string(&mine).unwrap(), "mine");
+            assert_eq!(std::fs::read_to_string(&written).unwrap(), "original");
+            assert_eq!(backup_path(&written).exists(), false);
+
+            std::fs::remove_dir_all(&dir).ok();
+        }
+
+        #[test]
+        fn it_refuses_to_overwrite_files_with_stale_backups() {
+            let shell = TestShell {};
+            let (out, err, mut output) = setup();
+            let dir = std::env::temp_dir().join(format!("sauce-stale-{}", std::process::id()));
+            std::fs::create_dir_all(&dir).unwrap();
+            let mine = dir.join(".env");
+            std::fs::write(&mine, "mine").unwrap();
+            std::fs::write(backup_path(&mine), "stale").unwrap();
+
+            let mut saucefile = Saucefile::default();
+            let section = ensure_section(&mut saucefile.document, "file");
+            section[".env"] = value_from_string("\"A=1\"");
+
+            execute(
+                &mut output,
+                &shell,
+                &saucefile,
+                &Settings::default(),
+                &FilterOptions::default(),
+                false,
+                &dir,
+                &LoadedState::default(),
+            );
+
+            assert_eq!(std::fs::read_to_string(&mine).unwrap(), "mine");
+            assert_eq!(
+                std::fs::read_to_string(backup_path(&mine)).unwrap(),
+                "stale"
+            );
+            assert!(err.value().contains("Not writing"));
+            assert!(!out.value().contains("files ="));
+
+            std::fs::remove_dir_all(&dir).ok();
+        }
+
+        #[test]
+        fn it_leaves_files_alone_when_their_templates_fail() {
+            let shell = TestShell {};
+            let (_, _, mut output) = setup();
+            let dir = std::env::temp_dir().join(format!("sauce-template-{}", std::process::id()));
+            std::fs::create_dir_all(&dir).unwrap();
+            let mine = dir.join(".env");
+            std::fs::write(&mine, "mine").unwrap();
+
+            let mut saucefile = Saucefile {
+                config_dir: dir.join("nonexistent"),
+                ..Default::default()
+            };
+            let section = ensure_section(&mut saucefile.document, "file");
+            section[".env"] = value_from_string("{template-file = \"env.tmpl\"}");
+
+            execute(
+                &mut output,
+                &shell,
+                &saucefile,
+                &Settings::default(),
+                &FilterOptions::default(),
+                false,
+                &dir,
+                &LoadedState::default(),
+            );
+
+            assert_eq!(std::fs::read_to_string(&mine).unwrap(), "mine");
+            assert_eq!(backup_path(&mine).exists(), false);
+
+            std::fs::remove_dir_all(&dir).ok();
+        }
+
+        #[test]
+        fn it_only_describes_writes_in_show_mode() {
+            let (_, err, output) = setup();
+            let mut output = output.show(true);
+            let dir = Path::new("/nonexistent/sauce");
+
+            let files = vec![("foo", "bar".to_string())];
+            write_files(&mut output, dir, files, None, &LoadedState::default());
+
+            assert_eq!(err.value(), "Would write /nonexistent/sauce/foo\nbar");
+            assert_eq!(dir.exists(), false);
+        }
+    }
 }
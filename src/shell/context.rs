@@ -7,13 +7,16 @@ use toml_edit::Item;
 
 use crate::{
     colors::{BLUE, RED, YELLOW},
+    export::ExportFormat,
     filter::FilterOptions,
-    output::Output,
+    import::{Imported, OnConflict},
+    output::{ErrorCode, Output},
     saucefile::Saucefile,
     settings::Settings,
     shell::{actions, Shell},
+    state::LoadedState,
     target::Target,
-    toml::value_from_string,
+    toml::{has_value, value_from_string},
 };
 
 #[derive(Debug)]
@@ -23,6 +26,7 @@ pub struct Context<'a> {
     corpus: corpus::Corpus,
     config_dir: PathBuf,
     path: PathBuf,
+    loaded: LoadedState,
 
     _sauce_path: Option<PathBuf>,
     _settings: Option<Settings>,
@@ -55,6 +59,7 @@ impl<'a> Context<'a> {
             config_dir,
             filter_options,
             path,
+            loaded: LoadedState::from_env(),
             _sauce_path: file.map(|p| p.to_path_buf()),
             _saucefile: None,
             _settings: None,
@@ -71,10 +76,42 @@ impl<'a> Context<'a> {
 
     fn load_saucefile(&mut self, output: &mut Output) {
         if self._saucefile.is_none() {
-            self._saucefile = Some(Saucefile::read(output, self.cascade_paths()));
+            let mut saucefile = Saucefile::read(output, self.cascade_paths());
+            saucefile.directories = saucefile
+                .paths()
+                .map(|p| (p.clone(), self.source_directory(p)))
+                .collect();
+            saucefile.config_dir = self.config_dir.clone();
+            saucefile.use_profiles(output);
+            self._saucefile = Some(saucefile);
         }
     }
 
+    /// The directory targeted by the saucefile at `sauce_path`.
+    fn source_directory(&self, sauce_path: &Path) -> PathBuf {
+        if self._sauce_path.is_some() {
+            // An explicit `--file` has no corresponding location, so it targets
+            // the location being sauce'd.
+            return self.path.clone();
+        }
+
+        if sauce_path.strip_prefix(&self.corpus.root_location).is_err() {
+            // The root saucefile (i.e. `sauce.toml`) sits beside, rather than within
+            // the corpus root.
+            return self.corpus.relative_path.clone();
+        }
+
+        self.corpus
+            .get_source_path(sauce_path)
+            .unwrap_or_else(|| self.path.clone())
+    }
+
+    /// The (absolute) location being sauce'd, against which `[file]` targets are
+    /// written.
+    fn sauced_directory(&self) -> PathBuf {
+        std::path::absolute(&self.path).unwrap_or_else(|_| self.path.clone())
+    }
+
     fn saucefile(&self) -> &Saucefile {
         self._saucefile.as_ref().unwrap()
     }
@@ -98,6 +135,11 @@ impl<'a> Context<'a> {
         self
     }
 
+    pub fn with_loaded_state(mut self, loaded: LoadedState) -> Self {
+        self.loaded = loaded;
+        self
+    }
+
     pub fn at_path<P: Into<PathBuf>>(mut self, path: P) -> Self {
         self.path = path.into();
         self
@@ -150,9 +192,88 @@ impl<'a> Context<'a> {
         actions::edit(output, shell_kind, &path);
     }
 
-    pub fn show(&mut self, target: Target, output: &mut Output) {
+    pub fn show(&mut self, target: Target, origin: bool, output: &mut Output) {
+        self.load_saucefile(output);
+        let data_dir = origin.then_some(self.corpus.root_location.as_path());
+        actions::show(
+            output,
+            &self.filter_options,
+            target,
+            self.saucefile(),
+            data_dir,
+        );
+    }
+
+    pub fn exec(&mut self, command: &[String], pure: bool, output: &mut Output) {
+        self.load_saucefile(output);
+        actions::exec(
+            output,
+            self.saucefile(),
+            &self.filter_options,
+            command,
+            pure,
+        );
+    }
+
+    pub fn run_function(
+        &mut self,
+        shell_kind: &dyn Shell,
+        function: &str,
+        args: &[String],
+        output: &mut Output,
+    ) {
         self.load_saucefile(output);
-        actions::show(output, &self.filter_options, target, self.saucefile());
+        actions::run(
+            output,
+            shell_kind,
+            self.saucefile(),
+            &self.filter_options,
+            function,
+            args,
+        );
+    }
+
+    pub fn list_functions(&mut self, output: &mut Output) {
+        self.load_saucefile(output);
+        actions::list_functions(output, self.saucefile(), &self.filter_options);
+    }
+
+    pub fn export(&mut self, format: ExportFormat, file: Option<&Path>, output: &mut Output) {
+        self.load_saucefile(output);
+        actions::export(output, &self.filter_options, self.saucefile(), format, file);
+    }
+
+    pub fn which(&mut self, key: &str, json: bool, output: &mut Output) {
+        self.load_settings(output);
+        self.load_saucefile(output);
+        actions::which(
+            output,
+            &self.filter_options,
+            self.saucefile(),
+            self.settings(),
+            key,
+            &self.corpus.root_location,
+            json,
+        );
+    }
+
+    pub fn status(&self, output: &mut Output) {
+        let loaded = &self.loaded;
+        if loaded.paths.is_empty() && loaded.is_empty() {
+            output.notify(&[BLUE.bold().paint("Nothing is sauced")]);
+            return;
+        }
+
+        let tags = loaded.tags.join(", ");
+        let mut message =
+            materialize_path_message("Sauced", &self.corpus.root_location, loaded.paths.iter());
+        if !tags.is_empty() {
+            message.push(BLUE.bold().paint(" as "));
+            message.push(YELLOW.paint(tags));
+        }
+        output.notify(&message);
+
+        actions::status(output, loaded);
     }
 
     pub fn clear(&mut self, shell_kind: &dyn Shell, output: &mut Output) {
@@ -165,6 +286,8 @@ impl<'a> Context<'a> {
             self.saucefile(),
             self.settings(),
             &self.filter_options,
+            &self.sauced_directory(),
+            &self.loaded,
         );
     }
 
@@ -180,6 +303,8 @@ impl<'a> Context<'a> {
             self.settings(),
             &self.filter_options,
             autoload,
+            &self.sauced_directory(),
+            &self.loaded,
         );
 
         if !sauced {
@@ -240,15 +365,141 @@ impl<'a> Context<'a> {
         self.set_values(output, "file", values);
     }
 
+    pub fn unset_values<T: AsRef<str>>(
+        &mut self,
+        target: Target,
+        names: &[T],
+        output: &mut Output,
+    ) {
+        self.load_saucefile(output);
+
+        let path = self.sauce_path();
+        let tags = self.filter_options.as_.clone();
+        let document = &mut self.saucefile_mut().document;
+
+        output.remove_toml(&path, document, target.sections(), names, tags.as_deref());
+    }
+
+    /// Imports the env vars and aliases assigned by the `.env` (or `.envrc`-style)
+    /// `file` into the targeted saucefile.
+    pub fn import(&mut self, file: &Path, on_conflict: OnConflict, output: &mut Output) {
+        self.load_saucefile(output);
+
+        let content = match std::fs::read_to_string(file) {
+            Ok(content) => content,
+            Err(_) => {
+                output.notify_error(
+                    ErrorCode::ParseError,
+                    &[
+                        RED.bold().paint("Couldn't read "),
+                        YELLOW.paint(file.to_string_lossy()),
+                    ],
+                );
+                return;
+            }
+        };
+
+        let imported = Imported::parse(&content);
+        for (line, text) in imported.skipped.iter() {
+            output.notify(&[
+                YELLOW.paint(format!("Skipping unsupported line {}: ", line)),
+                YELLOW.bold().paint(text),
+            ]);
+        }
+
+        let tags = self.filter_options.as_.clone();
+        let document = &self.saucefile().document;
+        let sections = [
+            (Target::EnvVar, "environment", imported.vars),
+            (Target::Alias, "alias", imported.aliases),
+        ];
+
+        // Values are written into whichever section already defines them, if any.
+        let mut values = Vec::new();
+        let mut conflicts = Vec::new();
+        for (target, default_section, pairs) in sections {
+            for (name, value) in pairs {
+                // Imported values are literal, so anything resembling a `${NAME}`
+                // reference is escaped, rather than later being interpolated.
+                let value = match target {
+                    Target::EnvVar => value.replace("${", "$${"),
+                    _ => value,
+                };
+                let existing = target.sections().iter().find_map(|section| {
+                    let table = document[section].as_table()?;
+                    table.contains_key(&name).then_some((*section, table))
+                });
+                let section = existing.map_or(default_section, |(section, _)| section);
+                let conflict =
+                    existing.is_some_and(|(_, table)| has_value(table, &name, tags.as_deref()));
+
+                if conflict {
+                    conflicts.push(name.clone());
+                    if on_conflict == OnConflict::Skip {
+                        continue;
+                    }
+                }
+                values.push((section, name, value));
+            }
+        }
+
+        match on_conflict {
+            OnConflict::Abort if !conflicts.is_empty() => {
+                output.notify_error(
+                    ErrorCode::WriteError,
+                    &[
+                        RED.paint("Refusing to overwrite "),
+                        YELLOW.bold().paint(conflicts.join(", ")),
+                        RED.paint(", use --overwrite or --skip to import anyway"),
+                    ],
+                );
+                return;
+            }
+            OnConflict::Skip if !conflicts.is_empty() => {
+                output.notify(&[
+                    YELLOW.paint("Skipping existing "),
+                    YELLOW.bold().paint(conflicts.join(", ")),
+                ]);
+            }
+            _ => {}
+        }
+
+        if values.is_empty() {
+            output.notify(&[BLUE.bold().paint("Nothing to import")]);
+            return;
+        }
+
+        for section in values.iter().map(|(section, _, _)| *section).unique() {
+            let section_values = values
+                .iter()
+                .filter(|(s, _, _)| *s == section)
+                .map(|(_, name, value)| (name, toml_edit::value(value.as_str())))
+                .collect::<Vec<_>>();
+            self.set_values(output, section, section_values);
+        }
+    }
+
     fn set_values<I, T>(&mut self, output: &mut Output, section: &str, values: I)
     where
         I: IntoIterator<Item = (T, Item)>,
         T: AsRef<str>,
     {
         let path = self.sauce_path();
+        let tags = self.filter_options.as_.clone();
         let document = &mut self.saucefile_mut().document;
 
-        output.write_toml(&path, document, section, values);
+        output.write_toml(&path, document, section, values, tags.as_deref());
+    }
+
+    pub fn show_settings(&mut self, output: &mut Output) {
+        self.load_settings(output);
+        self.load_saucefile(output);
+        actions::show_settings(
+            output,
+            self.saucefile(),
+            self.settings(),
+            &self.corpus.root_location,
+        );
     }
 
     pub fn set_config<T: AsRef<str>>(
@@ -302,6 +553,7 @@ impl<'a> Default for Context<'a> {
             corpus: corpus::builder().build().unwrap(),
             config_dir: PathBuf::new(),
             path: PathBuf::new(),
+            loaded: LoadedState::default(),
             _sauce_path: None,
             _saucefile: None,
             _settings: None,
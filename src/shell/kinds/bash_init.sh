@@ -1,3 +1,22 @@
 function {0} {{
+  # Subcommands which run (or print) something of their own are passed through,
+  # rather than having their output evaluated.
+  local arg skip
+  for arg in "$@"; do
+    if [ -n "$skip" ]; then
+      skip=
+      continue
+    fi
+    case "$arg" in
+      --color|--path|--file|--as|--glob|--target|--filter|-p|-a|-g|-t|-f) skip=1 ;;
+      -*) ;;
+      exec|run|export|which)
+        command {1} --shell bash "$@"
+        return
+        ;;
+      *) break ;;
+    esac
+  done
+
   eval "$(command {1} --shell bash "$@")"
 }}
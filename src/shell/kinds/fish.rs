@@ -1,5 +1,6 @@
-use crate::shell::utilities::{escape, qualify_binary_path};
+use crate::shell::utilities::{is_bare_word, qualify_binary_path, state_var};
 use crate::shell::Shell;
+use crate::value::PathPosition;
 use std::fmt::Write;
 
 pub struct Fish;
@@ -9,6 +10,16 @@ impl Shell for Fish {
         "fish"
     }
 
+    /// Within fish's single quotes, backslashes escape both single quotes and
+    /// themselves.
+    fn quote(&self, value: &str) -> String {
+        if is_bare_word(value) {
+            value.to_string()
+        } else {
+            format!("'{}'", value.replace('\\', "\\\\").replace('\'', "\\'"))
+        }
+    }
+
     fn init(&self, binary: &str, autoload_hook: bool, autoload_args: &str) -> String {
         let mut init = format!(
             include_str!("fish_init.fish"),
@@ -29,11 +40,11 @@ impl Shell for Fish {
     }
 
     fn set_var(&self, var: &str, value: &str) -> String {
-        format!("set -gx {} {}", var, escape(value))
+        format!("set -gx {} {}", var, self.quote(value))
     }
 
     fn set_alias(&self, var: &str, value: &str) -> String {
-        format!("alias {} {}", var, escape(value))
+        format!("alias {} {}", var, self.quote(value))
     }
 
     fn set_function(&self, var: &str, value: &str) -> String {
@@ -51,6 +62,85 @@ impl Shell for Fish {
     fn unset_function(&self, var: &str) -> String {
         format!("functions --erase {}", var)
     }
+
+    fn save_var(&self, var: &str) -> String {
+        format!(
+            "if not set -q {0}; set -g {0} 1; set -q {2}; and set -g {1} ${2}; end",
+            state_var("tracked", "var", var),
+            state_var("saved", "var", var),
+            var,
+        )
+    }
+
+    fn restore_var(&self, var: &str) -> String {
+        format!(
+            "if set -q {0}; set -gx {2} ${0}; else; {3}; end; set -e {0} {1}",
+            state_var("saved", "var", var),
+            state_var("tracked", "var", var),
+            var,
+            self.unset_var(var),
+        )
+    }
+
+    fn save_alias(&self, var: &str) -> String {
+        // Fish aliases are simply functions.
+        self.save_function(var)
+    }
+
+    fn restore_alias(&self, var: &str) -> String {
+        self.restore_function(var)
+    }
+
+    fn save_function(&self, var: &str) -> String {
+        format!(
+            "if not set -q {0}; set -g {0} 1; functions -q {2}; and functions --copy {2} {1}; end",
+            state_var("tracked", "function", var),
+            state_var("saved", "function", var),
+            var,
+        )
+    }
+
+    fn restore_function(&self, var: &str) -> String {
+        format!(
+            "{3}; if functions -q {0}; functions --copy {0} {2}; functions --erase {0}; end; set -e {1}",
+            state_var("saved", "function", var),
+            state_var("tracked", "function", var),
+            var,
+            self.unset_function(var),
+        )
+    }
+
+    fn add_path(&self, var: &str, position: PathPosition, entries: &[String]) -> String {
+        let entries = entries
+            .iter()
+            .map(|e| self.quote(e))
+            .collect::<Vec<_>>()
+            .join(" ");
+        match position {
+            PathPosition::Prepend => format!("set -gx {0} {1} ${0}", var, entries),
+            PathPosition::Append => format!("set -gx {0} ${0} {1}", var, entries),
+        }
+    }
+
+    fn remove_path(&self, var: &str, position: PathPosition, entries: &[String]) -> String {
+        let indices = match position {
+            PathPosition::Prepend => format!("(seq 1 (count ${}))", var),
+            PathPosition::Append => format!("(seq (count ${}) -1 1)", var),
+        };
+
+        entries
+            .iter()
+            .map(|entry| {
+                format!(
+                    "for index in {1}\n  if test \"${0}[$index]\" = {2}\n    set -e {0}[$index]\n    break\n  end\nend",
+                    var,
+                    indices,
+                    self.quote(entry),
+                )
+            })
+            .collect::<Vec<_>>()
+            .join("\n")
+    }
 }
 
 #[cfg(test)]
@@ -65,7 +155,7 @@ mod tests {
         fn it_edits_path() {
             let shell = Fish {};
             let output = shell.edit(Some(OsString::from("foo")), "foo/bar");
-            assert_eq!(output, Some(r#"foo 'foo/bar'"#.to_string()));
+            assert_eq!(output, Some("foo foo/bar".to_string()));
         }
     }
 
@@ -78,8 +168,20 @@ mod tests {
             let shell = Fish {};
             let output = shell.init("foo", false, "");
             assert_eq!(
-                output,
-                "function foo\n  eval (command foo --shell fish $argv)\nend\n"
+                output.contains("eval (command foo --shell fish $argv)"),
+                true
+            );
+            assert_eq!(output.contains("--autoload"), false);
+        }
+
+        #[test]
+        fn it_passes_through_commands_which_run_something() {
+            let shell = Fish {};
+            let output = shell.init("foo", false, "");
+            assert_eq!(output.contains("case exec run export which"), true);
+            assert_eq!(
+                output.contains("command foo --shell fish $argv\n        return $status"),
+                true
             );
         }
 
@@ -127,6 +229,74 @@ mod tests {
         }
     }
 
+    mod add_path {
+        use super::super::*;
+        use pretty_assertions::assert_eq;
+
+        #[test]
+        fn it_prepends() {
+            let shell = Fish {};
+            let output = shell.add_path(
+                "PATH",
+                PathPosition::Prepend,
+                &["/a".to_string(), "/b c".to_string()],
+            );
+            assert_eq!(output, "set -gx PATH /a '/b c' $PATH");
+        }
+
+        #[test]
+        fn it_appends() {
+            let shell = Fish {};
+            let output = shell.add_path("PATH", PathPosition::Append, &["/a".to_string()]);
+            assert_eq!(output, "set -gx PATH $PATH /a");
+        }
+    }
+
+    mod remove_path {
+        use super::super::*;
+        use pretty_assertions::assert_eq;
+
+        #[test]
+        fn it_removes_the_last_appended_entry() {
+            let shell = Fish {};
+            let output = shell.remove_path("PATH", PathPosition::Append, &["/a".to_string()]);
+            assert_eq!(
+                output,
+                "for index in (seq (count $PATH) -1 1)\n  if test \"$PATH[$index]\" = /a\n    set -e PATH[$index]\n    break\n  end\nend"
+            );
+        }
+    }
+
+    mod save_var {
+        use super::super::*;
+        use pretty_assertions::assert_eq;
+
+        #[test]
+        fn it_works() {
+            let shell = Fish {};
+            let output = shell.save_var("foo");
+            assert_eq!(
+                output,
+                "if not set -q _sauce_tracked_var_foo; set -g _sauce_tracked_var_foo 1; set -q foo; and set -g _sauce_saved_var_foo $foo; end"
+            );
+        }
+    }
+
+    mod restore_function {
+        use super::super::*;
+        use pretty_assertions::assert_eq;
+
+        #[test]
+        fn it_works() {
+            let shell = Fish {};
+            let output = shell.restore_function("foo");
+            assert_eq!(
+                output,
+                "functions --erase foo; if functions -q _sauce_saved_function_foo; functions --copy _sauce_saved_function_foo foo; functions --erase _sauce_saved_function_foo; end; set -e _sauce_tracked_function_foo"
+            );
+        }
+    }
+
     mod unset_var {
         use super::super::*;
         use pretty_assertions::assert_eq;
@@ -1,3 +1,23 @@
 function {0}
+  # Subcommands which run (or print) something of their own are passed through,
+  # rather than having their output evaluated.
+  set -l skip 0
+  for arg in $argv
+    if test $skip = 1
+      set skip 0
+      continue
+    end
+    switch $arg
+      case --color --path --file --as --glob --target --filter -p -a -g -t -f
+        set skip 1
+      case exec run export which
+        command {1} --shell fish $argv
+        return $status
+      case '-*'
+      case '*'
+        break
+    end
+  end
+
   eval (command {1} --shell fish $argv)
 end
@@ -1,7 +1,11 @@
 pub mod bash;
 pub mod fish;
+pub mod nushell;
+pub mod powershell;
 pub mod zsh;
 
 pub use bash::Bash;
 pub use fish::Fish;
+pub use nushell::Nushell;
+pub use powershell::PowerShell;
 pub use zsh::Zsh;
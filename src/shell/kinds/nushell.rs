@@ -0,0 +1,456 @@
+use crate::shell::utilities::{qualify_binary_path, state_var};
+use crate::shell::Shell;
+use crate::value::PathPosition;
+use serde_json::{json, Value};
+use std::ffi::OsString;
+use std::fmt::Write;
+
+/// Nushell can't `eval` shell text, so rather than code, each statement is a JSON
+/// record (on its own line), which the `sauce` command defined by `init` applies to
+/// the environment.
+pub struct Nushell;
+
+impl Nushell {
+    fn record(&self, record: Value) -> String {
+        record.to_string()
+    }
+
+    /// Nushell source, which the `sauce` command defined by `init` runs in the REPL's
+    /// own scope (as a `pre_prompt` hook), where aliases and functions can be defined.
+    fn code(&self, code: &str) -> String {
+        self.record(json!({"op": "code", "code": code}))
+    }
+}
+
+impl Shell for Nushell {
+    fn name(&self) -> &'static str {
+        "nu"
+    }
+
+    fn quote(&self, value: &str) -> String {
+        Value::from(value).to_string()
+    }
+
+    fn script(&self) -> Option<Box<dyn Shell>> {
+        Some(Box::new(NushellScript))
+    }
+
+    fn edit(&self, editor: Option<OsString>, path: &str) -> Option<String> {
+        editor.map(|e| self.record(json!({"op": "run", "command": [e.to_string_lossy(), path]})))
+    }
+
+    fn init(&self, binary: &str, autoload_hook: bool, autoload_args: &str) -> String {
+        let mut init = format!(
+            include_str!("nushell_init.nu"),
+            binary,
+            qualify_binary_path(binary),
+        );
+
+        if autoload_hook {
+            write!(
+                init,
+                include_str!("nushell_init_autoload.nu"),
+                binary, autoload_args,
+            )
+            .ok();
+        }
+
+        init
+    }
+
+    fn set_var(&self, var: &str, value: &str) -> String {
+        self.record(json!({"op": "set", "name": var, "value": value}))
+    }
+
+    fn unset_var(&self, var: &str) -> String {
+        self.record(json!({"op": "unset", "name": var}))
+    }
+
+    fn save_var(&self, var: &str) -> String {
+        self.record(json!({
+            "op": "save",
+            "name": var,
+            "tracked": state_var("tracked", "var", var),
+            "saved": state_var("saved", "var", var),
+        }))
+    }
+
+    fn restore_var(&self, var: &str) -> String {
+        self.record(json!({
+            "op": "restore",
+            "name": var,
+            "tracked": state_var("tracked", "var", var),
+            "saved": state_var("saved", "var", var),
+        }))
+    }
+
+    // Aliases and functions are defined (and hidden) by code written as in a script.
+    // Nushell has no way to save a prior definition, so restoring one hides it.
+    fn set_alias(&self, var: &str, value: &str) -> String {
+        self.code(&NushellScript.set_alias(var, value))
+    }
+
+    fn unset_alias(&self, var: &str) -> String {
+        self.code(&format!("hide {}", var))
+    }
+
+    fn save_alias(&self, _var: &str) -> String {
+        String::new()
+    }
+
+    fn restore_alias(&self, var: &str) -> String {
+        self.unset_alias(var)
+    }
+
+    fn set_function(&self, var: &str, value: &str) -> String {
+        self.code(&NushellScript.set_function(var, value))
+    }
+
+    fn unset_function(&self, var: &str) -> String {
+        self.code(&format!("hide {}", var))
+    }
+
+    fn save_function(&self, _var: &str) -> String {
+        String::new()
+    }
+
+    fn restore_function(&self, var: &str) -> String {
+        self.unset_function(var)
+    }
+
+    fn hook(&self, _name: &str, command: &str) -> String {
+        self.code(command)
+    }
+
+    fn add_path(&self, var: &str, position: PathPosition, entries: &[String]) -> String {
+        self.record(json!({
+            "op": "path",
+            "name": var,
+            "position": position.name(),
+            "entries": entries,
+        }))
+    }
+
+    fn remove_path(&self, var: &str, position: PathPosition, entries: &[String]) -> String {
+        self.record(json!({
+            "op": "unpath",
+            "name": var,
+            "position": position.name(),
+            "entries": entries,
+        }))
+    }
+}
+
+/// Nushell source, for scripts which `nu` runs itself (i.e. `sauce run`), within
+/// which aliases and functions can be defined, as the whole script is parsed
+/// up front.
+///
+/// Scripts run in a fresh process, so nothing they set is ever saved or restored.
+pub struct NushellScript;
+
+impl Shell for NushellScript {
+    fn name(&self) -> &'static str {
+        "nu"
+    }
+
+    /// Quotes `value` as a raw string, within which nothing is special, using
+    /// enough `#`s that the value can't end it early.
+    fn quote(&self, value: &str) -> String {
+        let mut hashes = "#".to_string();
+        while value.contains(&format!("'{}", hashes)) {
+            hashes.push('#');
+        }
+        format!("r{0}'{1}'{0}", hashes, value)
+    }
+
+    fn init(&self, _binary: &str, _autoload_hook: bool, _autoload_args: &str) -> String {
+        String::new()
+    }
+
+    fn set_var(&self, var: &str, value: &str) -> String {
+        format!("$env.{} = {}", var, self.quote(value))
+    }
+
+    fn unset_var(&self, var: &str) -> String {
+        format!("hide-env -i {}", var)
+    }
+
+    fn save_var(&self, _var: &str) -> String {
+        String::new()
+    }
+
+    fn restore_var(&self, _var: &str) -> String {
+        String::new()
+    }
+
+    fn set_alias(&self, var: &str, value: &str) -> String {
+        format!("alias {} = {}", var, value)
+    }
+
+    fn unset_alias(&self, _var: &str) -> String {
+        String::new()
+    }
+
+    fn save_alias(&self, _var: &str) -> String {
+        String::new()
+    }
+
+    fn restore_alias(&self, _var: &str) -> String {
+        String::new()
+    }
+
+    fn set_function(&self, var: &str, value: &str) -> String {
+        format!(
+            "def --wrapped {} [...args] {{\n  {}\n}}",
+            var,
+            value.replace('\n', "\n  ")
+        )
+    }
+
+    fn unset_function(&self, _var: &str) -> String {
+        String::new()
+    }
+
+    fn save_function(&self, _var: &str) -> String {
+        String::new()
+    }
+
+    fn restore_function(&self, _var: &str) -> String {
+        String::new()
+    }
+
+    fn add_path(&self, var: &str, position: PathPosition, entries: &[String]) -> String {
+        let entries = entries
+            .iter()
+            .map(|e| self.quote(e))
+            .collect::<Vec<_>>()
+            .join(" ");
+        let existing = format!("($env.{}? | default [] | split row (char esep))", var);
+        let combined = match position {
+            PathPosition::Prepend => format!("[{}] | append {}", entries, existing),
+            PathPosition::Append => format!("{} | append [{}]", existing, entries),
+        };
+        format!("$env.{} = ({} | str join (char esep))", var, combined)
+    }
+
+    fn remove_path(&self, _var: &str, _position: PathPosition, _entries: &[String]) -> String {
+        String::new()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    mod edit {
+        use std::ffi::OsString;
+
+        use super::super::*;
+        use pretty_assertions::assert_eq;
+
+        #[test]
+        fn it_runs_the_editor() {
+            let shell = Nushell {};
+            let output = shell.edit(Some(OsString::from("foo")), "foo/bar");
+            assert_eq!(
+                output,
+                Some(r#"{"command":["foo","foo/bar"],"op":"run"}"#.to_string())
+            );
+        }
+    }
+
+    mod init {
+        use super::super::*;
+        use pretty_assertions::assert_eq;
+
+        #[test]
+        fn it_defaults() {
+            let shell = Nushell {};
+            let output = shell.init("foo", false, "");
+            assert_eq!(output.contains("def --env foo [...args] {"), true);
+            assert_eq!(output.contains("^foo --shell nu ...$args"), true);
+            assert_eq!(output.contains("--autoload"), false);
+        }
+
+        #[test]
+        fn it_passes_through_commands_which_run_something() {
+            let shell = Nushell {};
+            let output = shell.init("foo", false, "");
+            assert_eq!(
+                output.contains("$passthrough = $arg in ['exec' 'run' 'export' 'which']"),
+                true
+            );
+            assert_eq!(
+                output.contains("^foo --shell nu ...$args\n    return"),
+                true
+            );
+        }
+
+        #[test]
+        fn it_runs_code_as_pre_prompt_hooks() {
+            let shell = Nushell {};
+            let output = shell.init("foo", false, "");
+            assert_eq!(
+                output.contains(r#""code" => { $code = ($code | append $record.code) }"#),
+                true
+            );
+            assert_eq!(
+                output.contains("$env.config.hooks.pre_prompt = ($env.config.hooks.pre_prompt? | default [] | append $hooks)"),
+                true
+            );
+        }
+
+        #[test]
+        fn it_includes_autoload() {
+            let shell = Nushell {};
+            let output = shell.init("foo", true, "");
+            assert_eq!(output.contains("foo --autoload"), true);
+        }
+    }
+
+    mod set_var {
+        use super::super::*;
+        use pretty_assertions::assert_eq;
+
+        #[test]
+        fn it_works() {
+            let shell = Nushell {};
+            let output = shell.set_var("foo", "it's \"bar\"\n");
+            assert_eq!(
+                output,
+                r#"{"name":"foo","op":"set","value":"it's \"bar\"\n"}"#
+            );
+        }
+    }
+
+    mod set_alias {
+        use super::super::*;
+        use pretty_assertions::assert_eq;
+
+        #[test]
+        fn it_defines_the_alias_in_code() {
+            let shell = Nushell {};
+            let output = shell.set_alias("foo", "git status");
+            assert_eq!(output, r#"{"code":"alias foo = git status","op":"code"}"#);
+        }
+    }
+
+    mod set_function {
+        use super::super::*;
+        use pretty_assertions::assert_eq;
+
+        #[test]
+        fn it_defines_the_function_in_code() {
+            let shell = Nushell {};
+            let output = shell.set_function("foo", "echo $args");
+            assert_eq!(
+                output,
+                r#"{"code":"def --wrapped foo [...args] {\n  echo $args\n}","op":"code"}"#
+            );
+        }
+    }
+
+    mod restore_function {
+        use super::super::*;
+        use pretty_assertions::assert_eq;
+
+        #[test]
+        fn it_hides_the_function() {
+            let shell = Nushell {};
+            let output = shell.restore_function("foo");
+            assert_eq!(output, r#"{"code":"hide foo","op":"code"}"#);
+        }
+    }
+
+    mod hook {
+        use super::super::*;
+        use pretty_assertions::assert_eq;
+
+        #[test]
+        fn it_runs_the_hook_as_code() {
+            let shell = Nushell {};
+            let output = shell.hook("on_enter", "overlay use .venv/bin/activate.nu");
+            assert_eq!(
+                output,
+                r#"{"code":"overlay use .venv/bin/activate.nu","op":"code"}"#
+            );
+        }
+    }
+
+    mod save_var {
+        use super::super::*;
+        use pretty_assertions::assert_eq;
+
+        #[test]
+        fn it_works() {
+            let shell = Nushell {};
+            let output = shell.save_var("foo");
+            assert_eq!(
+                output,
+                r#"{"name":"foo","op":"save","saved":"_sauce_saved_var_foo","tracked":"_sauce_tracked_var_foo"}"#
+            );
+        }
+    }
+
+    mod add_path {
+        use super::super::*;
+        use pretty_assertions::assert_eq;
+
+        #[test]
+        fn it_prepends() {
+            let shell = Nushell {};
+            let output = shell.add_path("PATH", PathPosition::Prepend, &["/a".to_string()]);
+            assert_eq!(
+                output,
+                r#"{"entries":["/a"],"name":"PATH","op":"path","position":"prepend"}"#
+            );
+        }
+    }
+
+    mod quote {
+        use super::super::*;
+        use pretty_assertions::assert_eq;
+
+        #[test]
+        fn it_quotes_json_strings() {
+            let shell = Nushell {};
+            assert_eq!(shell.quote("it's \"bar\"\n\\"), r#""it's \"bar\"\n\\""#);
+        }
+    }
+
+    mod script {
+        use super::super::*;
+        use pretty_assertions::assert_eq;
+
+        #[test]
+        fn it_quotes_raw_strings() {
+            let shell = NushellScript {};
+            assert_eq!(shell.quote("it's \"bar\""), "r#'it's \"bar\"'#");
+            assert_eq!(shell.quote("a'#b"), "r##'a'#b'##");
+        }
+
+        #[test]
+        fn it_sets_vars() {
+            let shell = NushellScript {};
+            assert_eq!(shell.set_var("foo", "bar"), "$env.foo = r#'bar'#");
+            assert_eq!(shell.unset_var("foo"), "hide-env -i foo");
+        }
+
+        #[test]
+        fn it_defines_functions_and_aliases() {
+            let shell = NushellScript {};
+            assert_eq!(
+                shell.set_function("greet", "print hi\nprint $args"),
+                "def --wrapped greet [...args] {\n  print hi\n  print $args\n}"
+            );
+            assert_eq!(shell.set_alias("g", "git status"), "alias g = git status");
+        }
+
+        #[test]
+        fn it_adds_paths() {
+            let shell = NushellScript {};
+            assert_eq!(
+                shell.add_path("PATH", PathPosition::Prepend, &["/bin".to_string()]),
+                "$env.PATH = ([r#'/bin'#] | append ($env.PATH? | default [] | split row (char esep)) | str join (char esep))"
+            );
+        }
+    }
+}
@@ -0,0 +1,416 @@
+use crate::shell::utilities::{qualify_binary_path, state_var};
+use crate::shell::Shell;
+use crate::value::PathPosition;
+use std::ffi::OsString;
+use std::fmt::Write;
+
+pub struct PowerShell;
+
+impl Shell for PowerShell {
+    fn name(&self) -> &'static str {
+        "pwsh"
+    }
+
+    /// Quotes `value` in single quotes, within which nothing is special but the single
+    /// quote itself (of which PowerShell also accepts the typographic variants), which
+    /// is escaped by doubling it.
+    fn quote(&self, value: &str) -> String {
+        let mut result = String::from("'");
+        for c in value.chars() {
+            if matches!(c, '\'' | '\u{2018}' | '\u{2019}' | '\u{201a}' | '\u{201b}') {
+                result.push(c);
+            }
+            result.push(c);
+        }
+        result.push('\'');
+        result
+    }
+
+    fn edit(&self, editor: Option<OsString>, path: &str) -> Option<String> {
+        editor.map(|e| {
+            format!(
+                "& {} {}",
+                self.quote(&e.to_string_lossy()),
+                self.quote(path)
+            )
+        })
+    }
+
+    fn init(&self, binary: &str, autoload_hook: bool, autoload_args: &str) -> String {
+        let mut init = format!(
+            include_str!("powershell_init.ps1"),
+            binary,
+            qualify_binary_path(binary),
+        );
+
+        if autoload_hook {
+            write!(
+                init,
+                include_str!("powershell_init_autoload.ps1"),
+                binary, autoload_args,
+            )
+            .ok();
+        }
+
+        init
+    }
+
+    fn set_var(&self, var: &str, value: &str) -> String {
+        format!("${{env:{}}} = {}", var, self.quote(value))
+    }
+
+    /// PowerShell aliases can only name a command, so aliases which include
+    /// arguments are defined as functions which forward any further arguments.
+    ///
+    /// The function's body is parsed from a quoted string, such that (like an
+    /// alias elsewhere) the value is only run when the alias is, rather than
+    /// being able to close the definition early.
+    fn set_alias(&self, var: &str, value: &str) -> String {
+        if value.contains(char::is_whitespace) {
+            format!(
+                "Set-Item -Path {} -Value ([scriptblock]::Create({}))",
+                self.quote(&format!("function:global:{}", var)),
+                self.quote(&format!("{} @args", value))
+            )
+        } else {
+            format!(
+                "Set-Alias -Scope Global -Force -Name {} -Value {}",
+                self.quote(var),
+                self.quote(value)
+            )
+        }
+    }
+
+    fn set_function(&self, var: &str, value: &str) -> String {
+        format!(
+            "function global:{} {{\n  {}\n}}",
+            var,
+            value.replace('\n', "\n  ")
+        )
+    }
+
+    fn unset_var(&self, var: &str) -> String {
+        format!(
+            "Remove-Item -LiteralPath {} -ErrorAction SilentlyContinue",
+            self.quote(&format!("env:{}", var))
+        )
+    }
+
+    fn unset_alias(&self, var: &str) -> String {
+        format!(
+            "Remove-Item -LiteralPath {}, {} -Force -ErrorAction SilentlyContinue",
+            self.quote(&format!("alias:{}", var)),
+            self.quote(&format!("function:{}", var)),
+        )
+    }
+
+    fn unset_function(&self, var: &str) -> String {
+        format!(
+            "Remove-Item -LiteralPath {} -ErrorAction SilentlyContinue",
+            self.quote(&format!("function:{}", var))
+        )
+    }
+
+    fn save_var(&self, var: &str) -> String {
+        format!(
+            "if (-not (Test-Path variable:{0})) {{ $global:{0} = 1; if (Test-Path -LiteralPath {3}) {{ $global:{1} = ${{env:{2}}} }} }}",
+            state_var("tracked", "var", var),
+            state_var("saved", "var", var),
+            var,
+            self.quote(&format!("env:{}", var)),
+        )
+    }
+
+    fn restore_var(&self, var: &str) -> String {
+        format!(
+            "if (Test-Path variable:{0}) {{ ${{env:{2}}} = $global:{0} }} else {{ {3} }}; Remove-Variable -Scope Global -Name {0}, {1} -ErrorAction SilentlyContinue",
+            state_var("saved", "var", var),
+            state_var("tracked", "var", var),
+            var,
+            self.unset_var(var),
+        )
+    }
+
+    fn save_alias(&self, var: &str) -> String {
+        format!(
+            "if (-not (Test-Path variable:{0})) {{ $global:{0} = 1; $global:{1} = Get-Item -LiteralPath {2} -ErrorAction SilentlyContinue }}",
+            state_var("tracked", "alias", var),
+            state_var("saved", "alias", var),
+            self.quote(&format!("alias:{}", var)),
+        )
+    }
+
+    fn restore_alias(&self, var: &str) -> String {
+        format!(
+            "{3}; if ($global:{0}) {{ Set-Alias -Scope Global -Force -Name {2} -Value $global:{0}.Definition }}; Remove-Variable -Scope Global -Name {0}, {1} -ErrorAction SilentlyContinue",
+            state_var("saved", "alias", var),
+            state_var("tracked", "alias", var),
+            self.quote(var),
+            self.unset_alias(var),
+        )
+    }
+
+    fn save_function(&self, var: &str) -> String {
+        format!(
+            "if (-not (Test-Path variable:{0})) {{ $global:{0} = 1; if (Test-Path -LiteralPath {3}) {{ $global:{1} = ${{function:{2}}} }} }}",
+            state_var("tracked", "function", var),
+            state_var("saved", "function", var),
+            var,
+            self.quote(&format!("function:{}", var)),
+        )
+    }
+
+    fn restore_function(&self, var: &str) -> String {
+        format!(
+            "{3}; if (Test-Path variable:{0}) {{ ${{function:global:{2}}} = $global:{0} }}; Remove-Variable -Scope Global -Name {0}, {1} -ErrorAction SilentlyContinue",
+            state_var("saved", "function", var),
+            state_var("tracked", "function", var),
+            var,
+            self.unset_function(var),
+        )
+    }
+
+    fn add_path(&self, var: &str, position: PathPosition, entries: &[String]) -> String {
+        let entries = self.quote(&entries.join(":"));
+        match position {
+            PathPosition::Prepend => format!(
+                "${{env:{0}}} = if (${{env:{0}}}) {{ {1} + ':' + ${{env:{0}}} }} else {{ {1} }}",
+                var, entries
+            ),
+            PathPosition::Append => format!(
+                "${{env:{0}}} = if (${{env:{0}}}) {{ ${{env:{0}}} + ':' + {1} }} else {{ {1} }}",
+                var, entries
+            ),
+        }
+    }
+
+    fn remove_path(&self, var: &str, position: PathPosition, entries: &[String]) -> String {
+        let find = match position {
+            PathPosition::Prepend => "IndexOf",
+            PathPosition::Append => "LastIndexOf",
+        };
+
+        let mut lines = vec![format!(
+            "$__sauce_path = [Collections.Generic.List[string]](${{env:{}}} -split ':')",
+            var
+        )];
+        for entry in entries {
+            lines.push(format!(
+                "$__sauce_index = $__sauce_path.{}({}); if ($__sauce_index -ge 0) {{ $__sauce_path.RemoveAt($__sauce_index) }}",
+                find,
+                self.quote(entry),
+            ));
+        }
+        lines.push(format!("${{env:{}}} = $__sauce_path -join ':'", var));
+        lines.push(
+            "Remove-Variable __sauce_path, __sauce_index -ErrorAction SilentlyContinue".to_string(),
+        );
+        lines.join("\n")
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    mod quote {
+        use super::super::*;
+        use pretty_assertions::assert_eq;
+
+        #[test]
+        fn it_doubles_single_quotes() {
+            let output = PowerShell {}.quote("it's $HOME `n \"x\"\n");
+            assert_eq!(output, "'it''s $HOME `n \"x\"\n'");
+        }
+
+        #[test]
+        fn it_doubles_typographic_single_quotes() {
+            let output = PowerShell {}.quote("it\u{2019}s");
+            assert_eq!(output, "'it\u{2019}\u{2019}s'");
+        }
+    }
+
+    mod edit {
+        use std::ffi::OsString;
+
+        use super::super::*;
+        use pretty_assertions::assert_eq;
+
+        #[test]
+        fn it_edits_path() {
+            let shell = PowerShell {};
+            let output = shell.edit(Some(OsString::from("foo")), "foo/bar");
+            assert_eq!(output, Some("& 'foo' 'foo/bar'".to_string()));
+        }
+    }
+
+    mod init {
+        use super::super::*;
+        use pretty_assertions::assert_eq;
+
+        #[test]
+        fn it_defaults() {
+            let shell = PowerShell {};
+            let output = shell.init("foo", false, "");
+            assert_eq!(output.contains("Invoke-Expression $__sauce"), true);
+            assert_eq!(output.contains("--autoload"), false);
+        }
+
+        #[test]
+        fn it_passes_through_commands_which_run_something() {
+            let shell = PowerShell {};
+            let output = shell.init("foo", false, "");
+            assert_eq!(
+                output.contains("-cin 'exec', 'run', 'export', 'which'"),
+                true
+            );
+            assert_eq!(
+                output.contains("& foo --shell pwsh @args\n      return"),
+                true
+            );
+        }
+
+        #[test]
+        fn it_includes_autoload() {
+            let shell = PowerShell {};
+            let output = shell.init("foo", true, "");
+            assert_eq!(output.contains("foo --autoload"), true);
+        }
+    }
+
+    mod set_var {
+        use super::super::*;
+        use pretty_assertions::assert_eq;
+
+        #[test]
+        fn it_works() {
+            let shell = PowerShell {};
+            let output = shell.set_var("foo", "bar");
+            assert_eq!(output, "${env:foo} = 'bar'");
+        }
+    }
+
+    mod set_alias {
+        use super::super::*;
+        use pretty_assertions::assert_eq;
+
+        #[test]
+        fn it_sets_aliases() {
+            let shell = PowerShell {};
+            let output = shell.set_alias("foo", "bar");
+            assert_eq!(
+                output,
+                "Set-Alias -Scope Global -Force -Name 'foo' -Value 'bar'"
+            );
+        }
+
+        #[test]
+        fn it_defines_functions_for_aliases_with_arguments() {
+            let shell = PowerShell {};
+            let output = shell.set_alias("foo", "git status");
+            assert_eq!(
+                output,
+                "Set-Item -Path 'function:global:foo' -Value ([scriptblock]::Create('git status @args'))"
+            );
+        }
+
+        #[test]
+        fn it_keeps_alias_values_within_the_function() {
+            let shell = PowerShell {};
+            let output = shell.set_alias("foo", "git status }; rm it; { '");
+            assert_eq!(
+                output,
+                "Set-Item -Path 'function:global:foo' -Value ([scriptblock]::Create('git status }; rm it; { '' @args'))"
+            );
+        }
+    }
+
+    mod set_function {
+        use super::super::*;
+        use pretty_assertions::assert_eq;
+
+        #[test]
+        fn it_works() {
+            let shell = PowerShell {};
+            let output = shell.set_function("foo", "bar\nbaz");
+            assert_eq!(output, "function global:foo {\n  bar\n  baz\n}");
+        }
+    }
+
+    mod unset_var {
+        use super::super::*;
+        use pretty_assertions::assert_eq;
+
+        #[test]
+        fn it_works() {
+            let shell = PowerShell {};
+            let output = shell.unset_var("foo");
+            assert_eq!(
+                output,
+                "Remove-Item -LiteralPath 'env:foo' -ErrorAction SilentlyContinue"
+            );
+        }
+    }
+
+    mod save_var {
+        use super::super::*;
+        use pretty_assertions::assert_eq;
+
+        #[test]
+        fn it_works() {
+            let shell = PowerShell {};
+            let output = shell.save_var("foo");
+            assert_eq!(
+                output,
+                "if (-not (Test-Path variable:_sauce_tracked_var_foo)) { $global:_sauce_tracked_var_foo = 1; if (Test-Path -LiteralPath 'env:foo') { $global:_sauce_saved_var_foo = ${env:foo} } }"
+            );
+        }
+    }
+
+    mod restore_var {
+        use super::super::*;
+        use pretty_assertions::assert_eq;
+
+        #[test]
+        fn it_works() {
+            let shell = PowerShell {};
+            let output = shell.restore_var("foo");
+            assert_eq!(
+                output,
+                "if (Test-Path variable:_sauce_saved_var_foo) { ${env:foo} = $global:_sauce_saved_var_foo } else { Remove-Item -LiteralPath 'env:foo' -ErrorAction SilentlyContinue }; Remove-Variable -Scope Global -Name _sauce_saved_var_foo, _sauce_tracked_var_foo -ErrorAction SilentlyContinue"
+            );
+        }
+    }
+
+    mod add_path {
+        use super::super::*;
+        use pretty_assertions::assert_eq;
+
+        #[test]
+        fn it_prepends() {
+            let shell = PowerShell {};
+            let output = shell.add_path(
+                "PATH",
+                PathPosition::Prepend,
+                &["/a".to_string(), "/b c".to_string()],
+            );
+            assert_eq!(
+                output,
+                "${env:PATH} = if (${env:PATH}) { '/a:/b c' + ':' + ${env:PATH} } else { '/a:/b c' }"
+            );
+        }
+    }
+
+    mod remove_path {
+        use super::super::*;
+        use pretty_assertions::assert_eq;
+
+        #[test]
+        fn it_removes_the_last_appended_entry() {
+            let shell = PowerShell {};
+            let output = shell.remove_path("PATH", PathPosition::Append, &["/a".to_string()]);
+            assert_eq!(
+                output,
+                "$__sauce_path = [Collections.Generic.List[string]](${env:PATH} -split ':')\n$__sauce_index = $__sauce_path.LastIndexOf('/a'); if ($__sauce_index -ge 0) { $__sauce_path.RemoveAt($__sauce_index) }\n${env:PATH} = $__sauce_path -join ':'\nRemove-Variable __sauce_path, __sauce_index -ErrorAction SilentlyContinue"
+            );
+        }
+    }
+}
@@ -0,0 +1,21 @@
+function global:{0} {{
+  # Subcommands which run (or print) something of their own are passed through,
+  # rather than having their output evaluated.
+  $__sauce_skip = $false
+  foreach ($__sauce_arg in $args) {{
+    if ($__sauce_skip) {{ $__sauce_skip = $false; continue }}
+    if ($__sauce_arg -cin '--color', '--path', '--file', '--as', '--glob', '--target', '--filter', '-p', '-a', '-g', '-t', '-f') {{
+      $__sauce_skip = $true
+      continue
+    }}
+    if ("$__sauce_arg".StartsWith('-')) {{ continue }}
+    if ($__sauce_arg -cin 'exec', 'run', 'export', 'which') {{
+      & {1} --shell pwsh @args
+      return
+    }}
+    break
+  }}
+
+  $__sauce = & {1} --shell pwsh @args | Out-String
+  if ($__sauce) {{ Invoke-Expression $__sauce }}
+}}
@@ -0,0 +1,10 @@
+# PowerShell has no hook for changing directories, so the prompt is wrapped to
+# autoload whenever the location has changed since it was last drawn.
+$global:__sauce_prompt = $function:prompt
+function global:prompt {{
+  if ($global:__sauce_location -ne $PWD.Path) {{
+    $global:__sauce_location = $PWD.Path
+    {0}{1} --autoload
+  }}
+  & $global:__sauce_prompt
+}}
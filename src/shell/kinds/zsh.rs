@@ -1,4 +1,4 @@
-use crate::shell::utilities::{escape, qualify_binary_path};
+use crate::shell::utilities::{qualify_binary_path, state_var};
 use crate::shell::Shell;
 use std::fmt::Write;
 
@@ -28,28 +28,26 @@ impl Shell for Zsh {
         init
     }
 
-    fn set_var(&self, var: &str, value: &str) -> String {
-        format!("export {}={}", var, escape(value))
-    }
-
-    fn set_alias(&self, var: &str, value: &str) -> String {
-        format!("alias {}={}", var, escape(value))
-    }
-
     fn set_function(&self, var: &str, value: &str) -> String {
         format!("function {} {{\n  {}\n}}", var, value.replace('\n', "\n  "))
     }
 
-    fn unset_var(&self, var: &str) -> String {
-        format!("unset {}", var)
+    fn save_alias(&self, var: &str) -> String {
+        format!(
+            "if [ -z \"${{{0}+x}}\" ]; then {0}=1; if alias {2} >/dev/null 2>&1; then {1}=\"$(alias -L {2})\"; fi; fi",
+            state_var("tracked", "alias", var),
+            state_var("saved", "alias", var),
+            var,
+        )
     }
 
-    fn unset_alias(&self, var: &str) -> String {
-        format!("unalias {} 2>/dev/null", var)
-    }
-
-    fn unset_function(&self, var: &str) -> String {
-        format!("unset -f {}", var)
+    fn save_function(&self, var: &str) -> String {
+        format!(
+            "if [ -z \"${{{0}+x}}\" ]; then {0}=1; if (( ${{+functions[{2}]}} )); then {1}=\"$(functions {2})\"; fi; fi",
+            state_var("tracked", "function", var),
+            state_var("saved", "function", var),
+            var,
+        )
     }
 }
 
@@ -65,7 +63,7 @@ mod tests {
         fn it_edits_path() {
             let shell = Zsh {};
             let output = shell.edit(Some(OsString::from("foo")), "foo/bar");
-            assert_eq!(output, Some(r#"foo 'foo/bar'"#.to_string()));
+            assert_eq!(output, Some("foo foo/bar".to_string()));
         }
     }
 
@@ -78,8 +76,20 @@ mod tests {
             let shell = Zsh {};
             let output = shell.init("foo", false, "");
             assert_eq!(
-                output,
-                "function foo {\n  eval \"$(command foo --shell zsh \"$@\")\"\n}\n"
+                output.contains("eval \"$(command foo --shell zsh \"$@\")\""),
+                true
+            );
+            assert_eq!(output.contains("--autoload"), false);
+        }
+
+        #[test]
+        fn it_passes_through_commands_which_run_something() {
+            let shell = Zsh {};
+            let output = shell.init("foo", false, "");
+            assert_eq!(output.contains("exec|run|export|which)"), true);
+            assert_eq!(
+                output.contains("command foo --shell zsh \"$@\"\n        return"),
+                true
             );
         }
 
@@ -127,6 +137,60 @@ mod tests {
         }
     }
 
+    mod add_path {
+        use super::super::*;
+        use crate::value::PathPosition;
+        use pretty_assertions::assert_eq;
+
+        #[test]
+        fn it_prepends() {
+            let shell = Zsh {};
+            let output = shell.add_path(
+                "PATH",
+                PathPosition::Prepend,
+                &["/a".to_string(), "/b c".to_string()],
+            );
+            assert_eq!(output, "export PATH='/a:/b c'\"${PATH:+:$PATH}\"");
+        }
+
+        #[test]
+        fn it_appends() {
+            let shell = Zsh {};
+            let output = shell.add_path("PATH", PathPosition::Append, &["/a".to_string()]);
+            assert_eq!(output, "export PATH=\"${PATH:+$PATH:}\"/a");
+        }
+    }
+
+    mod restore_var {
+        use super::super::*;
+        use pretty_assertions::assert_eq;
+
+        #[test]
+        fn it_works() {
+            let shell = Zsh {};
+            let output = shell.restore_var("foo");
+            assert_eq!(
+                output,
+                "if [ -n \"${_sauce_saved_var_foo+x}\" ]; then export foo=$_sauce_saved_var_foo; else unset foo; fi; unset _sauce_saved_var_foo _sauce_tracked_var_foo"
+            );
+        }
+    }
+
+    mod save_function {
+        use super::super::*;
+        use pretty_assertions::assert_eq;
+
+        #[test]
+        fn it_works() {
+            let shell = Zsh {};
+            let output = shell.save_function("foo");
+            assert_eq!(
+                output,
+                "if [ -z \"${_sauce_tracked_function_foo+x}\" ]; then _sauce_tracked_function_foo=1; if (( ${+functions[foo]} )); then _sauce_saved_function_foo=\"$(functions foo)\"; fi; fi"
+            );
+        }
+    }
+
     mod unset_var {
         use super::super::*;
         use pretty_assertions::assert_eq;
@@ -2,34 +2,159 @@ mod actions;
 pub mod context;
 mod kinds;
 mod utilities;
-use crate::shell::utilities::escape;
+use crate::shell::utilities::{is_bare_word, state_var};
+use crate::value::PathPosition;
 
 use std::ffi::OsString;
 
-pub use kinds::{Bash, Fish, Zsh};
+pub use kinds::{Bash, Fish, Nushell, PowerShell, Zsh};
 pub use utilities::{detect, should_be_colored, ColorStrategy, ShellName};
 
 pub trait Shell {
     fn name(&self) -> &'static str;
 
+    /// Quotes `value` as a single word, which the shell takes literally.
+    ///
+    /// Defaults to POSIX quoting, i.e. in single quotes, within which nothing is
+    /// special but the single quote itself.
+    fn quote(&self, value: &str) -> String {
+        if is_bare_word(value) {
+            value.to_string()
+        } else {
+            format!("'{}'", value.replace('\'', "'\\''"))
+        }
+    }
+
     fn edit(&self, editor: Option<OsString>, path: &str) -> Option<String> {
-        editor.map(|e| format!("{} '{}'", e.to_string_lossy(), path))
+        editor.map(|e| format!("{} {}", e.to_string_lossy(), self.quote(path)))
     }
 
     fn init(&self, binary: &str, autoload: bool, autoload_args: &str) -> String;
-    fn set_var(&self, var: &str, value: &str) -> String;
-    fn unset_var(&self, var: &str) -> String;
 
-    fn set_alias(&self, var: &str, value: &str) -> String;
-    fn unset_alias(&self, var: &str) -> String;
+    /// Defaults to POSIX shell code, as do the other targets (save for saving a
+    /// function, which POSIX has no means of).
+    fn set_var(&self, var: &str, value: &str) -> String {
+        format!("export {}={}", var, self.quote(value))
+    }
+
+    fn unset_var(&self, var: &str) -> String {
+        format!("unset {}", var)
+    }
+
+    /// Records the current value of `var` (if any) before it's first overwritten,
+    /// so that `restore_var` can reinstate it.
+    ///
+    /// What's been saved is tracked in `_sauce_*` shell variables.
+    fn save_var(&self, var: &str) -> String {
+        format!(
+            "if [ -z \"${{{0}+x}}\" ]; then {0}=1; if [ -n \"${{{2}+x}}\" ]; then {1}=${2}; fi; fi",
+            state_var("tracked", "var", var),
+            state_var("saved", "var", var),
+            var,
+        )
+    }
 
-    fn set_function(&self, var: &str, value: &str) -> String;
-    fn unset_function(&self, var: &str) -> String;
+    /// Reinstates the value recorded by `save_var`, or unsets `var` if it had none.
+    fn restore_var(&self, var: &str) -> String {
+        let saved = state_var("saved", "var", var);
+        format!(
+            "if [ -n \"${{{0}+x}}\" ]; then export {2}=${0}; else {3}; fi; unset {0} {1}",
+            saved,
+            state_var("tracked", "var", var),
+            var,
+            self.unset_var(var),
+        )
+    }
 
-    fn set_file(&self, var: &str, value: &str) -> String {
-        format!("printf '{1}' > {0}", escape(var), value)
+    fn set_alias(&self, var: &str, value: &str) -> String {
+        format!("alias {}={}", var, self.quote(value))
     }
-    fn unset_file(&self, var: &str) -> String {
-        format!("rm '{}'", escape(var))
+
+    fn unset_alias(&self, var: &str) -> String {
+        format!("unalias {} 2>/dev/null", var)
+    }
+
+    fn save_alias(&self, var: &str) -> String {
+        format!(
+            "if [ -z \"${{{0}+x}}\" ]; then {0}=1; if alias {2} >/dev/null 2>&1; then {1}=\"$(alias {2})\"; fi; fi",
+            state_var("tracked", "alias", var),
+            state_var("saved", "alias", var),
+            var,
+        )
+    }
+
+    fn restore_alias(&self, var: &str) -> String {
+        let saved = state_var("saved", "alias", var);
+        format!(
+            "if [ -n \"${{{0}+x}}\" ]; then eval \"${0}\"; else {2}; fi; unset {0} {1}",
+            saved,
+            state_var("tracked", "alias", var),
+            self.unset_alias(var),
+        )
+    }
+
+    /// Defines the function `var`, whose body `value` is code in the shell's own
+    /// language, and is therefore included verbatim.
+    fn set_function(&self, var: &str, value: &str) -> String {
+        format!("{}() {{\n  {}\n}}", var, value.replace('\n', "\n  "))
+    }
+
+    fn unset_function(&self, var: &str) -> String {
+        format!("unset -f {}", var)
+    }
+
+    fn save_function(&self, var: &str) -> String;
+
+    fn restore_function(&self, var: &str) -> String {
+        let saved = state_var("saved", "function", var);
+        format!(
+            "if [ -n \"${{{0}+x}}\" ]; then eval \"${0}\"; else {2}; fi; unset {0} {1}",
+            saved,
+            state_var("tracked", "function", var),
+            self.unset_function(var),
+        )
+    }
+
+    /// The shell in which scripts (i.e. for `sauce run`) are written, for shells
+    /// whose output is otherwise applied by `init`'s wrapper, rather than run.
+    fn script(&self) -> Option<Box<dyn Shell>> {
+        None
+    }
+
+    /// Runs the `[hooks]` command `name` (i.e. `on_enter` or `on_leave`), which,
+    /// like a function body, is code in the shell's own language.
+    fn hook(&self, _name: &str, command: &str) -> String {
+        command.to_string()
+    }
+
+    fn add_path(&self, var: &str, position: PathPosition, entries: &[String]) -> String {
+        let entries = self.quote(&entries.join(":"));
+        match position {
+            PathPosition::Prepend => format!("export {0}={1}\"${{{0}:+:${0}}}\"", var, entries),
+            PathPosition::Append => format!("export {0}=\"${{{0}:+${0}:}}\"{1}", var, entries),
+        }
+    }
+
+    /// Removes exactly the `entries` added by `add_path`, i.e. the first (when
+    /// prepended) or last (when appended) occurrence of each.
+    fn remove_path(&self, var: &str, position: PathPosition, entries: &[String]) -> String {
+        let (before, after) = match position {
+            PathPosition::Prepend => ("%%", "#"),
+            PathPosition::Append => ("%", "##"),
+        };
+
+        let mut lines = vec![format!("__sauce_path=\":${{{}}}:\"", var)];
+        for entry in entries {
+            lines.push(format!(
+                "case $__sauce_path in *{0}*) __sauce_path=${{__sauce_path{1}{0}*}}:${{__sauce_path{2}*{0}}};; esac",
+                self.quote(&format!(":{}:", entry)),
+                before,
+                after,
+            ));
+        }
+        lines.push("__sauce_path=${__sauce_path#:}".to_string());
+        lines.push(format!("export {}=${{__sauce_path%:}}", var));
+        lines.push("unset __sauce_path".to_string());
+        lines.join("\n")
     }
 }
@@ -1,5 +1,5 @@
-use crate::shell::kinds::{Bash, Fish, Zsh};
-use std::{collections::VecDeque, str::FromStr};
+use crate::shell::kinds::{Bash, Fish, Nushell, PowerShell, Zsh};
+use std::str::FromStr;
 
 use crate::shell::Shell;
 
@@ -8,6 +8,8 @@ pub enum ShellName {
     Zsh,
     Fish,
     Bash,
+    Nushell,
+    PowerShell,
 }
 
 impl FromStr for ShellName {
@@ -18,8 +20,10 @@ impl FromStr for ShellName {
             "zsh" => Ok(Self::Zsh),
             "bash" => Ok(Self::Bash),
             "fish" => Ok(Self::Fish),
+            "nu" | "nushell" => Ok(Self::Nushell),
+            "pwsh" | "powershell" => Ok(Self::PowerShell),
             unhandled => Err(format!(
-                "Unrecognized shell '{}'. Valid options are: zsh, fish, bash",
+                "Unrecognized shell '{}'. Valid options are: zsh, fish, bash, nu, pwsh",
                 unhandled
             )),
         }
@@ -49,33 +53,32 @@ impl FromStr for ColorStrategy {
     }
 }
 
-pub fn unescape_newline(s: &str) -> String {
-    let mut queue: VecDeque<_> = String::from(s).chars().collect();
-    let mut s = String::new();
-
-    while let Some(c) = queue.pop_front() {
-        if c != '\\' {
-            s.push(c);
-            continue;
-        }
-
-        match queue.pop_front() {
-            Some('n') => s.push('\n'),
-            Some(c) => {
-                s.push('\\');
-                s.push(c);
+/// The name of a shell variable used to track sauce's state about the `kind` of
+/// target named `name`, i.e. `_sauce_saved_var_FOO`.
+///
+/// Target names may not be valid variable names (i.e. `work-push`), so any
+/// character outside of `[A-Za-z0-9]` is hex encoded.
+pub fn state_var(state: &str, kind: &str, name: &str) -> String {
+    let name: String = name
+        .chars()
+        .map(|c| {
+            if c.is_ascii_alphanumeric() {
+                c.to_string()
+            } else {
+                format!("_{:02x}", c as u32)
             }
-            _ => break,
-        };
-    }
-
-    s
+        })
+        .collect();
+    format!("_sauce_{}_{}_{}", state, kind, name)
 }
 
-/// Ensure proper quoting of any `value` being output to the containing shell.
-pub fn escape(value: &str) -> String {
-    let shell_value = snailquote::escape(value).to_string();
-    unescape_newline(&shell_value)
+/// Whether `value` can be output as-is, because none of its characters are special
+/// to any shell.
+pub fn is_bare_word(value: &str) -> bool {
+    !value.is_empty()
+        && value
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || "_./:,+-".contains(c))
 }
 
 pub fn qualify_binary_path(binary: &str) -> String {
@@ -96,6 +99,8 @@ pub fn detect(shell_name: ShellName) -> Box<dyn Shell> {
         ShellName::Zsh => Box::new(Zsh {}),
         ShellName::Fish => Box::new(Fish {}),
         ShellName::Bash => Box::new(Bash {}),
+        ShellName::Nushell => Box::new(Nushell {}),
+        ShellName::PowerShell => Box::new(PowerShell {}),
     }
 }
 
@@ -116,38 +121,31 @@ pub fn should_be_colored(strategy: ColorStrategy) -> bool {
 
 #[cfg(test)]
 mod tests {
-    mod escape {
+    mod state_var {
         use super::super::*;
         use pretty_assertions::assert_eq;
 
         #[test]
-        fn it_leaves_vanilla_string_unchanged() {
-            let result = escape("foo");
-            assert_eq!(result, "foo");
-        }
-
-        #[test]
-        fn it_single_quotes_spaces() {
-            let result = escape("foo bar");
-            assert_eq!(result, "'foo bar'");
+        fn it_encodes_non_alphanumeric_characters() {
+            let result = state_var("saved", "function", "work-push_2");
+            assert_eq!(result, "_sauce_saved_function_work_2dpush_5f2");
         }
+    }
 
-        #[test]
-        fn it_double_quotes_single_quotes() {
-            let result = escape("foo'bar");
-            assert_eq!(result, "\"foo'bar\"");
-        }
+    mod is_bare_word {
+        use super::super::*;
+        use pretty_assertions::assert_eq;
 
         #[test]
-        fn it_double_quotes_newlines() {
-            let result = escape("foo\nbar");
-            assert_eq!(result, "\"foo\nbar\"");
+        fn it_accepts_vanilla_strings() {
+            assert_eq!(is_bare_word("foo/bar-1.2"), true);
         }
 
         #[test]
-        fn it_doesnt_unescape_escaped_newlines() {
-            let result = escape("foo\\nbar");
-            assert_eq!(result, "\"foo\\\\nbar\"");
+        fn it_rejects_special_characters() {
+            for value in ["", "foo bar", "foo'bar", "~", "$foo", "foo\nbar", "=foo"] {
+                assert_eq!(is_bare_word(value), false, "{:?}", value);
+            }
         }
     }
 }
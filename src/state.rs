@@ -0,0 +1,433 @@
+use std::{fmt::Display, path::PathBuf};
+
+use indexmap::IndexMap;
+use sha2::{Digest, Sha256};
+use toml_edit::{Document, InlineTable, Value};
+
+use crate::value::PathPosition;
+
+/// Entries which were added to the list-like variable `var`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct LoadedPathEntries {
+    pub var: String,
+    pub position: PathPosition,
+    pub entries: Vec<String>,
+}
+
+/// An `on_leave` hook of the saucefile at `path`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct LoadedHook {
+    pub path: PathBuf,
+    pub hook: String,
+}
+
+/// Everything which `sauce` has loaded into the current shell.
+///
+/// The state is carried between invocations through the `SAUCE_LOADED` environment
+/// variable, so that values can be unloaded once we've moved away from the saucefiles
+/// which set them.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct LoadedState {
+    pub paths: Vec<PathBuf>,
+    /// The tags (i.e. `--as`) with which the values were loaded.
+    pub tags: Vec<String>,
+    /// The environment variables which were set, along with the [`digest`] of the
+    /// value they were set to. Only the digest is recorded, as `SAUCE_LOADED` is
+    /// exported to every child process, and the values may well be secrets.
+    pub vars: IndexMap<String, String>,
+    /// The environment variables which were unset.
+    pub unset: Vec<String>,
+    pub aliases: Vec<String>,
+    pub functions: Vec<String>,
+    pub files: Vec<String>,
+    pub path_entries: Vec<LoadedPathEntries>,
+    /// The `on_leave` hooks of the loaded saucefiles, in cascade order, which run
+    /// once the values are unloaded. They're recorded along with their saucefile, so
+    /// that the same hook in two saucefiles runs for each of them.
+    pub on_leave: Vec<LoadedHook>,
+}
+
+impl LoadedState {
+    pub const VAR: &'static str = "SAUCE_LOADED";
+
+    pub fn from_env() -> Self {
+        std::env::var(Self::VAR)
+            .map(|state| Self::parse(&state))
+            .unwrap_or_default()
+    }
+
+    /// Parses a previously rendered state. Anything unparseable is treated as though
+    /// nothing were loaded.
+    pub fn parse(state: &str) -> Self {
+        let document = match state.parse::<Document>() {
+            Ok(document) => document,
+            Err(_) => return Self::default(),
+        };
+
+        let strings = |key: &str| -> Vec<String> {
+            document[key]
+                .as_array()
+                .map(|array| {
+                    array
+                        .iter()
+                        .filter_map(|v| v.as_str().map(|s| s.to_string()))
+                        .collect()
+                })
+                .unwrap_or_default()
+        };
+
+        let vars = document["vars"]
+            .as_array()
+            .map(|array| {
+                array
+                    .iter()
+                    .filter_map(|v| v.as_inline_table())
+                    .filter_map(|table| {
+                        let name = table.get("name")?.as_str()?;
+                        let digest = table.get("digest")?.as_str()?;
+                        Some((name.to_string(), digest.to_string()))
+                    })
+                    .collect()
+            })
+            .unwrap_or_default();
+
+        let path_entries = document["path"]
+            .as_array()
+            .map(|array| {
+                array
+                    .iter()
+                    .filter_map(|v| v.as_inline_table())
+                    .filter_map(LoadedPathEntries::from_inline_table)
+                    .collect()
+            })
+            .unwrap_or_default();
+
+        let on_leave = document["on_leave"]
+            .as_array()
+            .map(|array| {
+                array
+                    .iter()
+                    .filter_map(|v| v.as_inline_table())
+                    .filter_map(|table| {
+                        Some(LoadedHook {
+                            path: PathBuf::from(table.get("path")?.as_str()?),
+                            hook: table.get("hook")?.as_str()?.to_string(),
+                        })
+                    })
+                    .collect()
+            })
+            .unwrap_or_default();
+
+        Self {
+            paths: strings("paths").into_iter().map(PathBuf::from).collect(),
+            tags: strings("tags"),
+            vars,
+            unset: strings("unset"),
+            aliases: strings("aliases"),
+            functions: strings("functions"),
+            files: strings("files"),
+            path_entries,
+            on_leave,
+        }
+    }
+
+    /// Whether no values at all are loaded.
+    pub fn is_empty(&self) -> bool {
+        self.vars.is_empty()
+            && self.unset.is_empty()
+            && self.aliases.is_empty()
+            && self.functions.is_empty()
+            && self.files.is_empty()
+            && self.path_entries.is_empty()
+            && self.on_leave.is_empty()
+    }
+
+    /// Whether any of the loaded saucefiles are absent from the cascade `paths`,
+    /// i.e. we've moved out of the tree which loaded them.
+    pub fn is_left(&self, paths: &[&PathBuf]) -> bool {
+        self.paths.iter().any(|path| !paths.contains(&path))
+    }
+
+    /// The values which are loaded in `self`, but not in `other`.
+    pub fn without(&self, other: &Self) -> Self {
+        let difference = |a: &[String], b: &[String]| -> Vec<String> {
+            a.iter().filter(|k| !b.contains(k)).cloned().collect()
+        };
+
+        Self {
+            paths: self.paths.clone(),
+            tags: self.tags.clone(),
+            vars: self
+                .vars
+                .iter()
+                .filter(|(k, _)| !other.vars.contains_key(*k))
+                .map(|(k, v)| (k.clone(), v.clone()))
+                .collect(),
+            unset: difference(&self.unset, &other.unset),
+            aliases: difference(&self.aliases, &other.aliases),
+            functions: difference(&self.functions, &other.functions),
+            files: difference(&self.files, &other.files),
+            path_entries: self
+                .path_entries
+                .iter()
+                .filter(|p| !other.path_entries.contains(p))
+                .cloned()
+                .collect(),
+            on_leave: self
+                .on_leave
+                .iter()
+                .filter(|h| !other.on_leave.contains(h))
+                .cloned()
+                .collect(),
+        }
+    }
+
+    /// The values which are loaded in either `self` or `other`, where `other` was
+    /// loaded more recently.
+    pub fn merge(&self, other: &Self) -> Self {
+        fn union<T: Clone + PartialEq>(a: &[T], b: &[T]) -> Vec<T> {
+            let mut result = a.to_vec();
+            result.extend(b.iter().filter(|v| !a.contains(v)).cloned());
+            result
+        }
+
+        Self {
+            paths: union(&self.paths, &other.paths),
+            tags: other.tags.clone(),
+            vars: self
+                .vars
+                .iter()
+                .filter(|(k, _)| !other.unset.contains(k))
+                .chain(other.vars.iter())
+                .map(|(k, v)| (k.clone(), v.clone()))
+                .collect(),
+            unset: union(&self.unset, &other.unset)
+                .into_iter()
+                .filter(|k| !other.vars.contains_key(k))
+                .collect(),
+            aliases: union(&self.aliases, &other.aliases),
+            functions: union(&self.functions, &other.functions),
+            files: union(&self.files, &other.files),
+            path_entries: union(&self.path_entries, &other.path_entries),
+            on_leave: union(&self.on_leave, &other.on_leave),
+        }
+    }
+}
+
+impl LoadedPathEntries {
+    fn from_inline_table(table: &InlineTable) -> Option<Self> {
+        let position = match table.get("position")?.as_str()? {
+            "prepend" => PathPosition::Prepend,
+            "append" => PathPosition::Append,
+            _ => return None,
+        };
+        let entries = table
+            .get("entries")?
+            .as_array()?
+            .iter()
+            .filter_map(|v| v.as_str().map(|s| s.to_string()))
+            .collect();
+
+        Some(Self {
+            var: table.get("var")?.as_str()?.to_string(),
+            position,
+            entries,
+        })
+    }
+
+    fn to_inline_table(&self) -> InlineTable {
+        let mut table = InlineTable::default();
+        table.get_or_insert("var", self.var.as_str());
+        table.get_or_insert("position", self.position.name());
+        table.get_or_insert(
+            "entries",
+            self.entries.iter().map(|e| e.as_str()).collect::<Value>(),
+        );
+        InlineTable::fmt(&mut table);
+        table
+    }
+}
+
+impl Display for LoadedState {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        fn insert<V: Into<Value>>(document: &mut Document, key: &str, values: Vec<V>) {
+            if !values.is_empty() {
+                document[key] = toml_edit::value(values.into_iter().collect::<Value>());
+            }
+        }
+
+        fn strings(values: &[String]) -> Vec<&str> {
+            values.iter().map(|v| v.as_str()).collect()
+        }
+
+        let mut document = Document::new();
+
+        let paths = self
+            .paths
+            .iter()
+            .map(|p| p.to_string_lossy())
+            .collect::<Vec<_>>();
+        insert(
+            &mut document,
+            "paths",
+            paths.iter().map(|p| p.as_ref()).collect(),
+        );
+        insert(&mut document, "tags", strings(&self.tags));
+        insert(
+            &mut document,
+            "vars",
+            self.vars
+                .iter()
+                .map(|(name, digest)| inline_table(&[("name", name), ("digest", digest)]))
+                .collect(),
+        );
+        insert(&mut document, "unset", strings(&self.unset));
+        insert(&mut document, "aliases", strings(&self.aliases));
+        insert(&mut document, "functions", strings(&self.functions));
+        insert(&mut document, "files", strings(&self.files));
+        insert(
+            &mut document,
+            "path",
+            self.path_entries
+                .iter()
+                .map(|p| p.to_inline_table())
+                .collect(),
+        );
+        insert(
+            &mut document,
+            "on_leave",
+            self.on_leave
+                .iter()
+                .map(|h| inline_table(&[("path", &h.path.to_string_lossy()), ("hook", &h.hook)]))
+                .collect(),
+        );
+
+        write!(f, "{}", document.to_string().trim_end())
+    }
+}
+
+/// A digest of the loaded `value` of some environment variable, by which we can
+/// tell whether it's since been changed without recording the value itself.
+pub fn digest(value: &str) -> String {
+    Sha256::digest(value.as_bytes())[..16]
+        .iter()
+        .map(|byte| format!("{:02x}", byte))
+        .collect()
+}
+
+fn inline_table(pairs: &[(&str, &str)]) -> InlineTable {
+    let mut table = InlineTable::default();
+    for (key, value) in pairs {
+        table.get_or_insert(key, *value);
+    }
+    InlineTable::fmt(&mut table);
+    table
+}
+
+#[cfg(test)]
+mod tests {
+    mod parse {
+        use super::super::*;
+        use pretty_assertions::assert_eq;
+
+        #[test]
+        fn it_round_trips() {
+            let state = LoadedState {
+                paths: vec![PathBuf::from("/data/sauce/a.toml")],
+                tags: vec!["prod".to_string()],
+                vars: IndexMap::from([
+                    ("FOO".to_string(), digest("foo")),
+                    ("BAR".to_string(), digest("multi\nline \"bar\"")),
+                ]),
+                unset: vec!["BAZ".to_string()],
+                aliases: vec!["g".to_string()],
+                functions: vec![],
+                files: vec![],
+                path_entries: vec![LoadedPathEntries {
+                    var: "PATH".to_string(),
+                    position: PathPosition::Prepend,
+                    entries: vec!["/a/bin".to_string()],
+                }],
+                on_leave: vec![LoadedHook {
+                    path: PathBuf::from("/data/sauce/a.toml"),
+                    hook: "deactivate".to_string(),
+                }],
+            };
+            assert_eq!(LoadedState::parse(&state.to_string()), state);
+        }
+
+        #[test]
+        fn it_ignores_garbage() {
+            assert_eq!(LoadedState::parse("not = [toml"), LoadedState::default());
+        }
+    }
+
+    mod digest {
+        use super::super::*;
+        use pretty_assertions::assert_eq;
+
+        #[test]
+        fn it_doesnt_record_the_value() {
+            let state = LoadedState {
+                vars: IndexMap::from([("TOKEN".to_string(), digest("hunter2"))]),
+                ..Default::default()
+            };
+            assert_eq!(state.to_string().contains("hunter2"), false);
+            assert_eq!(digest("hunter2"), digest("hunter2"));
+            assert_eq!(digest("hunter2") == digest("hunter3"), false);
+        }
+    }
+
+    mod without {
+        use super::super::*;
+        use pretty_assertions::assert_eq;
+
+        #[test]
+        fn it_keeps_values_missing_from_other() {
+            let state = LoadedState {
+                aliases: vec!["foo".to_string(), "bar".to_string()],
+                ..Default::default()
+            };
+            let other = LoadedState {
+                aliases: vec!["bar".to_string()],
+                ..Default::default()
+            };
+            assert_eq!(state.without(&other).aliases, vec!["foo".to_string()]);
+        }
+
+        #[test]
+        fn it_distinguishes_hooks_by_saucefile() {
+            let hook = |path: &str| LoadedHook {
+                path: PathBuf::from(path),
+                hook: "deactivate".to_string(),
+            };
+            let state = LoadedState {
+                on_leave: vec![hook("/sauce.toml"), hook("/sauce/a.toml")],
+                ..Default::default()
+            };
+            let other = LoadedState {
+                on_leave: vec![hook("/sauce.toml")],
+                ..Default::default()
+            };
+            assert_eq!(state.without(&other).on_leave, vec![hook("/sauce/a.toml")]);
+        }
+    }
+
+    mod is_left {
+        use super::super::*;
+        use pretty_assertions::assert_eq;
+
+        #[test]
+        fn it_checks_the_cascade() {
+            let root = PathBuf::from("/sauce.toml");
+            let a = PathBuf::from("/sauce/a.toml");
+            let b = PathBuf::from("/sauce/b.toml");
+            let state = LoadedState {
+                paths: vec![root.clone(), a.clone()],
+                ..Default::default()
+            };
+            assert_eq!(state.is_left(&[&root, &a]), false);
+            assert_eq!(state.is_left(&[&root, &b]), true);
+        }
+    }
+}
@@ -3,4 +3,18 @@ pub enum Target {
     Function,
     Alias,
     File,
+    Path,
+}
+
+impl Target {
+    /// The saucefile sections from which the target's values are read.
+    pub fn sections(&self) -> &'static [&'static str] {
+        match self {
+            Self::EnvVar => &["env", "environment"],
+            Self::Function => &["function"],
+            Self::Alias => &["alias"],
+            Self::File => &["file"],
+            Self::Path => &["path"],
+        }
+    }
 }
@@ -6,7 +6,7 @@ use std::{
     str,
 };
 
-use crate::{output::Output, shell::Shell};
+use crate::{output::Output, shell::Shell, value::PathPosition};
 
 pub fn mkpath(path: &str) -> PathBuf {
     Path::new(path).canonicalize().unwrap()
@@ -50,6 +50,38 @@ impl Shell for TestShell {
     fn unset_function(&self, var: &str) -> String {
         format!("unset {}", var)
     }
+
+    fn save_var(&self, var: &str) -> String {
+        format!("save {}", var)
+    }
+
+    fn restore_var(&self, var: &str) -> String {
+        format!("restore {}", var)
+    }
+
+    fn save_alias(&self, var: &str) -> String {
+        format!("save alias {}", var)
+    }
+
+    fn restore_alias(&self, var: &str) -> String {
+        format!("restore alias {}", var)
+    }
+
+    fn save_function(&self, var: &str) -> String {
+        format!("save function {}", var)
+    }
+
+    fn restore_function(&self, var: &str) -> String {
+        format!("restore function {}", var)
+    }
+
+    fn add_path(&self, var: &str, position: PathPosition, entries: &[String]) -> String {
+        format!("{} {}={}", position.name(), var, entries.join(":"))
+    }
+
+    fn remove_path(&self, var: &str, position: PathPosition, entries: &[String]) -> String {
+        format!("un{} {}={}", position.name(), var, entries.join(":"))
+    }
 }
 
 #[derive(Clone)]
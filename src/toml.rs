@@ -1,6 +1,7 @@
 use crate::{
     colors::{RED, YELLOW},
     output::{ErrorCode, Output},
+    value::TargetValue,
 };
 use std::{
     fs::OpenOptions,
@@ -8,7 +9,7 @@ use std::{
     str::FromStr,
 };
 use std::{io::Write, path::Path};
-use toml_edit::{Document, Item, Table, Value};
+use toml_edit::{Document, InlineTable, Item, Table, Value};
 
 pub fn get_document(path: &Path, output: &mut Output) -> Document {
     let content = read_file(path);
@@ -16,7 +17,7 @@ pub fn get_document(path: &Path, output: &mut Output) -> Document {
 }
 
 pub fn write_document(file: &Path, document: &Document, output: &mut Output) {
-    let handle = OpenOptions::new().write(true).open(file);
+    let handle = OpenOptions::new().write(true).truncate(true).open(file);
     write_contents(handle, file, document, output);
 }
 
@@ -88,6 +89,107 @@ pub fn ensure_section<'a>(document: &'a mut Document, section: &str) -> &'a mut
     env_section
 }
 
+/// Sets `key` in `table` to `value`, or when `tags` are given, only the entries for
+/// those tags in its table of tags. An existing untagged value is kept as the `default`
+/// entry, and setting an untagged value updates the `default` entry of a table of tags.
+pub fn set_value<T: AsRef<str>>(table: &mut Table, key: &str, value: Value, tags: Option<&[T]>) {
+    let default = ["default"];
+    let (tags, untagged) = match tags {
+        Some(tags) => (tags.iter().map(|t| t.as_ref()).collect::<Vec<_>>(), false),
+        None => (default.to_vec(), true),
+    };
+
+    match table.entry(key) {
+        Item::Value(Value::InlineTable(values)) if is_tag_table(values) => {
+            for tag in tags {
+                match values.get_mut(tag) {
+                    Some(existing) => *existing = value.clone(),
+                    None => {
+                        values.get_or_insert(tag, value.clone());
+                    }
+                }
+            }
+            InlineTable::fmt(values);
+        }
+        Item::Table(values) if TargetValue::from_table(values).is_none() => {
+            for tag in tags {
+                values[tag] = toml_edit::value(value.clone());
+            }
+        }
+        Item::Value(existing) if !untagged => {
+            let mut values = InlineTable::default();
+            values.get_or_insert("default", existing.clone());
+            for tag in tags {
+                values.get_or_insert(tag, value.clone());
+            }
+            InlineTable::fmt(&mut values);
+            *existing = toml_edit::decorated(Value::InlineTable(values), " ", "");
+        }
+        item if !untagged && item.is_none() => {
+            let mut values = InlineTable::default();
+            for tag in tags {
+                values.get_or_insert(tag, value.clone());
+            }
+            InlineTable::fmt(&mut values);
+            *item = toml_edit::value(values);
+        }
+        item => *item = toml_edit::value(value),
+    }
+}
+
+/// Whether an inline table is a table of tags, rather than a directive like
+/// `{ cmd = "..." }` or `{ template = "..." }`.
+fn is_tag_table(table: &InlineTable) -> bool {
+    TargetValue::from_inline_table(table).is_none()
+}
+
+/// Whether `set_value` would replace an existing value of `key`, i.e. whether `key`
+/// is defined, or when `tags` are given, whether any of those tags are.
+pub fn has_value<T: AsRef<str>>(table: &Table, key: &str, tags: Option<&[T]>) -> bool {
+    let tags = match tags {
+        Some(tags) => tags,
+        None => return table.contains_key(key),
+    };
+
+    match table.get(key) {
+        Some(Item::Value(Value::InlineTable(values))) if is_tag_table(values) => {
+            tags.iter().any(|t| values.contains_key(t.as_ref()))
+        }
+        Some(Item::Table(values)) if TargetValue::from_table(values).is_none() => {
+            tags.iter().any(|t| values.contains_key(t.as_ref()))
+        }
+        // A directive table is replaced outright, rather than gaining a tag.
+        Some(Item::Table(_)) => true,
+        _ => false,
+    }
+}
+
+/// Removes `key` from `table`, or when `tags` are given, only the entries for those
+/// tags from its table of tags. Returns whether anything was removed.
+pub fn remove_value<T: AsRef<str>>(table: &mut Table, key: &str, tags: Option<&[T]>) -> bool {
+    let tags = match tags {
+        Some(tags) => tags,
+        None => return table.remove(key).is_some(),
+    };
+
+    let (removed, is_empty) = match table.get_mut(key) {
+        Some(Item::Value(Value::InlineTable(values))) => {
+            let removed = tags.iter().filter(|t| values.remove(t.as_ref()).is_some());
+            (removed.count() > 0, values.is_empty())
+        }
+        Some(Item::Table(values)) => {
+            let removed = tags.iter().filter(|t| values.remove(t.as_ref()).is_some());
+            (removed.count() > 0, values.is_empty())
+        }
+        _ => (false, false),
+    };
+
+    if is_empty {
+        table.remove(key);
+    }
+    removed
+}
+
 pub fn value_from_string(raw_value: &str) -> Item {
     let value = Value::from_str(raw_value).unwrap_or_else(|_| Value::from(raw_value));
     toml_edit::value(value)
@@ -106,7 +208,147 @@ pub fn unwrap_toml_value(value: &Value) -> String {
 }
 
 #[cfg(test)]
+#[allow(clippy::io_other_error)]
 mod tests {
+    mod set_value {
+        use super::super::*;
+        use pretty_assertions::assert_eq;
+
+        fn set(toml: &str, tags: Option<&[&str]>) -> String {
+            let mut document = toml.parse::<Document>().expect("invalid doc");
+            let table = document["env"].as_table_mut().unwrap();
+            set_value(table, "FOO", Value::from("new"), tags);
+            document.to_string()
+        }
+
+        #[test]
+        fn it_replaces_plain_values() {
+            assert_eq!(
+                set("[env]\nFOO = \"old\"\n", None),
+                "[env]\nFOO = \"new\"\n"
+            );
+        }
+
+        #[test]
+        fn it_converts_plain_values_into_tags() {
+            assert_eq!(
+                set("[env]\nFOO = \"old\"\n", Some(&["prod"])),
+                "[env]\nFOO = { default = \"old\", prod = \"new\" }\n"
+            );
+        }
+
+        #[test]
+        fn it_updates_a_single_tag() {
+            assert_eq!(
+                set(
+                    "[env]\nFOO = {default = \"a\", prod = \"b\"}\n",
+                    Some(&["prod"])
+                ),
+                "[env]\nFOO = { default = \"a\", prod = \"new\" }\n"
+            );
+        }
+
+        #[test]
+        fn it_updates_the_default_tag_when_untagged() {
+            assert_eq!(
+                set("[env]\nFOO = {default = \"a\", prod = \"b\"}\n", None),
+                "[env]\nFOO = { default = \"new\", prod = \"b\" }\n"
+            );
+        }
+
+        #[test]
+        fn it_adds_tags_to_missing_keys() {
+            assert_eq!(
+                set("[env]\n", Some(&["prod"])),
+                "[env]\nFOO = { prod = \"new\" }\n"
+            );
+        }
+
+        #[test]
+        fn it_keeps_directives_as_the_default_tag() {
+            assert_eq!(
+                set("[env]\nFOO = { template = \"a\" }\n", Some(&["prod"])),
+                "[env]\nFOO = { default = { template = \"a\" }, prod = \"new\" }\n"
+            );
+        }
+    }
+
+    mod has_value {
+        use super::super::*;
+        use pretty_assertions::assert_eq;
+
+        #[test]
+        fn it_checks_keys_and_tags() {
+            let document = "[env]\nFOO = 1\nBAR = {default = 1, prod = 2}\n"
+                .parse::<Document>()
+                .expect("invalid doc");
+            let table = document["env"].as_table().unwrap();
+            assert_eq!(has_value::<&str>(table, "FOO", None), true);
+            assert_eq!(has_value::<&str>(table, "BAZ", None), false);
+            assert_eq!(has_value(table, "FOO", Some(&["prod"])), false);
+            assert_eq!(has_value(table, "BAR", Some(&["prod"])), true);
+            assert_eq!(has_value(table, "BAR", Some(&["ci"])), false);
+        }
+
+        #[test]
+        fn it_checks_directive_tables() {
+            let document = "[env.FOO]\ncmd = \"echo foo\"\n[env.BAR]\ntemplate-file = \"bar\"\n"
+                .parse::<Document>()
+                .expect("invalid doc");
+            let table = document["env"].as_table().unwrap();
+            assert_eq!(has_value(table, "FOO", Some(&["prod"])), true);
+            assert_eq!(has_value(table, "BAR", Some(&["prod"])), true);
+        }
+    }
+
+    mod remove_value {
+        use super::super::*;
+        use pretty_assertions::assert_eq;
+
+        fn remove(toml: &str, key: &str, tags: Option<&[&str]>) -> (bool, String) {
+            let mut document = toml.parse::<Document>().expect("invalid doc");
+            let table = document["env"].as_table_mut().unwrap();
+            let removed = remove_value(table, key, tags);
+            (removed, document.to_string())
+        }
+
+        #[test]
+        fn it_removes_keys() {
+            assert_eq!(
+                remove("[env]\nFOO = 1\nBAR = 2\n", "FOO", None),
+                (true, "[env]\nBAR = 2\n".to_string())
+            );
+        }
+
+        #[test]
+        fn it_removes_single_tags() {
+            assert_eq!(
+                remove(
+                    "[env]\nFOO = {default = 1, prod = 2}\n",
+                    "FOO",
+                    Some(&["prod"])
+                ),
+                (true, "[env]\nFOO = {default = 1}\n".to_string())
+            );
+        }
+
+        #[test]
+        fn it_removes_keys_without_remaining_tags() {
+            assert_eq!(
+                remove("[env]\nFOO = {prod = 2}\n", "FOO", Some(&["prod"])),
+                (true, "[env]\n".to_string())
+            );
+        }
+
+        #[test]
+        fn it_skips_missing_tags() {
+            assert_eq!(
+                remove("[env]\nFOO = 1\n", "FOO", Some(&["prod"])),
+                (false, "[env]\nFOO = 1\n".to_string())
+            );
+        }
+    }
+
     mod write_contents {
         use crate::test_utils::setup;
 
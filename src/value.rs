@@ -0,0 +1,434 @@
+use std::{fmt::Display, path::Path};
+
+use toml_edit::{InlineTable, Item, Table, Value};
+
+use crate::toml::unwrap_toml_value;
+
+/// A single (already tag-selected) value for some target.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum TargetValue {
+    /// A value which is used verbatim (after interpolation).
+    Literal(String),
+    /// A command, i.e. `{ cmd = "..." }`, whose stdout becomes the value.
+    Command(String),
+    /// A template, i.e. `{ template = "..." }`, whose `{{NAME}}` references are
+    /// rendered with the resolved env vars.
+    Template(String),
+    /// A template file, i.e. `{ template-file = "..." }`, relative to the config dir.
+    TemplateFile(String),
+    /// A directive, i.e. `{ unset = true }`, which removes any value defined by an
+    /// ancestor saucefile.
+    Unset,
+}
+
+impl TargetValue {
+    pub fn from_value(value: &Value) -> Self {
+        match value {
+            Value::InlineTable(table) => {
+                Self::from_inline_table(table).unwrap_or_else(|| Self::Literal(table.to_string()))
+            }
+            value => Self::Literal(unwrap_toml_value(value)),
+        }
+    }
+
+    /// Selects the value for the first matching tag out of `tags`.
+    ///
+    /// Values which are not tables of tags (or a directive like `{ cmd = ".." }`)
+    /// are untagged, and always selected.
+    pub fn select<T: AsRef<str>>(item: &Item, tags: &[T]) -> Option<Self> {
+        Self::select_tagged(item, tags).map(|(value, _)| value)
+    }
+
+    /// Selects the value per [`TargetValue::select`], along with the tag which
+    /// selected it (if the value was tagged).
+    pub fn select_tagged<'t, T: AsRef<str>>(
+        item: &Item,
+        tags: &'t [T],
+    ) -> Option<(Self, Option<&'t str>)> {
+        match item {
+            Item::Value(Value::InlineTable(table)) => Self::from_inline_table(table)
+                .map(|value| (value, None))
+                .or_else(|| {
+                    tags.iter().find_map(|tag| {
+                        let value = table.get(tag.as_ref())?;
+                        Some((Self::from_value(value), Some(tag.as_ref())))
+                    })
+                }),
+            Item::Value(value) => Some((Self::from_value(value), None)),
+            Item::Table(table) => {
+                Self::from_table(table)
+                    .map(|value| (value, None))
+                    .or_else(|| {
+                        tags.iter().find_map(|tag| {
+                            let value = table[tag.as_ref()].as_value()?;
+                            Some((Self::from_value(value), Some(tag.as_ref())))
+                        })
+                    })
+            }
+            _ => None,
+        }
+    }
+
+    /// Every variant of `item`, i.e. each value of a table of tags, or the single
+    /// untagged value.
+    pub fn variants(item: &Item) -> Vec<(Option<&str>, Self)> {
+        match item {
+            Item::Value(Value::InlineTable(table)) => match Self::from_inline_table(table) {
+                Some(value) => vec![(None, value)],
+                None => table
+                    .iter()
+                    .map(|(tag, value)| (Some(tag), Self::from_value(value)))
+                    .collect(),
+            },
+            Item::Value(value) => vec![(None, Self::from_value(value))],
+            Item::Table(table) => match Self::from_table(table) {
+                Some(value) => vec![(None, value)],
+                None => table
+                    .iter()
+                    .filter_map(|(tag, item)| Some((Some(tag), Self::from_value(item.as_value()?))))
+                    .collect(),
+            },
+            _ => Vec::new(),
+        }
+    }
+
+    /// The directive (i.e. `{ cmd = ".." }`) written as `table`, or `None` if it's a
+    /// table of tags.
+    pub fn from_inline_table(table: &InlineTable) -> Option<Self> {
+        if let Some(cmd) = table.get("cmd").and_then(|cmd| cmd.as_str()) {
+            return Some(Self::Command(cmd.to_string()));
+        }
+        if let Some(template) = table.get("template").and_then(|t| t.as_str()) {
+            return Some(Self::Template(template.to_string()));
+        }
+        if let Some(file) = table.get("template-file").and_then(|f| f.as_str()) {
+            return Some(Self::TemplateFile(file.to_string()));
+        }
+        match table.get("unset").and_then(|unset| unset.as_bool()) {
+            Some(true) => Some(Self::Unset),
+            _ => None,
+        }
+    }
+
+    /// The directive written as `table`, per [`TargetValue::from_inline_table`].
+    pub fn from_table(table: &Table) -> Option<Self> {
+        if let Some(cmd) = table["cmd"].as_str() {
+            return Some(Self::Command(cmd.to_string()));
+        }
+        if let Some(template) = table["template"].as_str() {
+            return Some(Self::Template(template.to_string()));
+        }
+        if let Some(file) = table["template-file"].as_str() {
+            return Some(Self::TemplateFile(file.to_string()));
+        }
+        match table["unset"].as_bool() {
+            Some(true) => Some(Self::Unset),
+            _ => None,
+        }
+    }
+}
+
+/// Reads the keys listed by an entry of the `[unset]` section, i.e. `env = ["FOO"]`,
+/// or the keys for the first matching tag out of `tags`, i.e. `env = { ci = ["FOO"] }`.
+pub fn unset_keys<'i, T: AsRef<str>>(item: &'i Item, tags: &[T]) -> Vec<&'i str> {
+    unset_keys_tagged(item, tags).0
+}
+
+/// Reads the keys per [`unset_keys`], along with the tag which selected them (if
+/// the keys were tagged).
+pub fn unset_keys_tagged<'i, 't, T: AsRef<str>>(
+    item: &'i Item,
+    tags: &'t [T],
+) -> (Vec<&'i str>, Option<&'t str>) {
+    let (array, tag) = match item {
+        Item::Value(Value::Array(array)) => (Some(array), None),
+        Item::Value(Value::InlineTable(table)) => tags
+            .iter()
+            .find_map(|tag| Some((table.get(tag.as_ref())?.as_array()?, tag.as_ref())))
+            .map_or((None, None), |(array, tag)| (Some(array), Some(tag))),
+        Item::Table(table) => tags
+            .iter()
+            .find_map(|tag| Some((table[tag.as_ref()].as_array()?, tag.as_ref())))
+            .map_or((None, None), |(array, tag)| (Some(array), Some(tag))),
+        _ => (None, None),
+    };
+
+    let keys = array
+        .map(|array| array.iter().filter_map(|v| v.as_str()).collect())
+        .unwrap_or_default();
+    (keys, tag)
+}
+
+/// The tags under which an entry of the `[unset]` section lists `key`, where an
+/// untagged list of keys yields `None`.
+pub fn unset_variants<'i>(item: &'i Item, key: &str) -> Vec<Option<&'i str>> {
+    let contains = |value: &Value| {
+        value
+            .as_array()
+            .map(|array| array.iter().any(|v| v.as_str() == Some(key)))
+            .unwrap_or(false)
+    };
+
+    match item {
+        Item::Value(Value::InlineTable(table)) => table
+            .iter()
+            .filter(|(_, value)| contains(value))
+            .map(|(tag, _)| Some(tag))
+            .collect(),
+        Item::Value(value) if contains(value) => vec![None],
+        Item::Table(table) => table
+            .iter()
+            .filter(|(_, item)| item.as_value().map(contains).unwrap_or(false))
+            .map(|(tag, _)| Some(tag))
+            .collect(),
+        _ => Vec::new(),
+    }
+}
+
+/// A single definition of some key, along with the saucefile (and tag) it came from.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Origin<'a> {
+    pub path: &'a Path,
+    /// The tag (i.e. `--as`) which selected the value, if it was tagged.
+    pub tag: Option<String>,
+    pub value: TargetValue,
+}
+
+/// Where entries are added to a list-like variable (e.g. `PATH`).
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum PathPosition {
+    Prepend,
+    Append,
+}
+
+impl PathPosition {
+    pub fn name(&self) -> &'static str {
+        match self {
+            Self::Prepend => "prepend",
+            Self::Append => "append",
+        }
+    }
+}
+
+/// Entries added to the list-like variable `var` by a single saucefile.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct PathEntries<'a> {
+    /// The saucefile which defined the entries.
+    pub origin: &'a Path,
+    pub var: &'a str,
+    pub position: PathPosition,
+    pub entries: Vec<String>,
+}
+
+impl<'a> PathEntries<'a> {
+    /// Reads the `[path]` section of a saucefile.
+    ///
+    /// Top-level `prepend`/`append` keys apply to `PATH`, whereas any other key
+    /// names the variable to which its own `prepend`/`append` keys apply, i.e.
+    /// `[path.PYTHONPATH]`.
+    pub fn from_table(origin: &'a Path, table: &'a Table) -> Vec<Self> {
+        let mut result = Self::from_positions(origin, "PATH", |position| &table[position.name()]);
+        for (var, item) in table.iter() {
+            if var != PathPosition::Prepend.name() && var != PathPosition::Append.name() {
+                result.extend(Self::from_positions(origin, var, |position| {
+                    &item[position.name()]
+                }));
+            }
+        }
+        result
+    }
+
+    fn from_positions<'i, F>(origin: &'a Path, var: &'a str, get: F) -> Vec<Self>
+    where
+        F: Fn(PathPosition) -> &'i Item,
+    {
+        [PathPosition::Prepend, PathPosition::Append]
+            .into_iter()
+            .filter_map(|position| {
+                let entries = match get(position) {
+                    Item::Value(Value::Array(array)) => array
+                        .iter()
+                        .filter_map(|v| v.as_str().map(|s| s.to_string()))
+                        .collect(),
+                    Item::Value(value) => vec![value.as_str()?.to_string()],
+                    _ => return None,
+                };
+                Some(Self {
+                    origin,
+                    var,
+                    position,
+                    entries,
+                })
+            })
+            .collect()
+    }
+}
+
+impl Display for TargetValue {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Literal(value) => write!(f, "{}", value),
+            Self::Command(command) => write!(f, "$({})", command),
+            Self::Template(template) => write!(f, "{}", template),
+            Self::TemplateFile(file) => write!(f, "(template {})", file),
+            Self::Unset => Ok(()),
+        }
+    }
+}
+
+impl From<&str> for TargetValue {
+    fn from(value: &str) -> Self {
+        Self::Literal(value.to_string())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    mod select {
+        use super::super::*;
+        use pretty_assertions::assert_eq;
+        use toml_edit::Document;
+
+        fn select(toml: &str, tags: &[&str]) -> Option<TargetValue> {
+            let document = toml.parse::<Document>().expect("invalid doc");
+            TargetValue::select(&document["foo"], tags)
+        }
+
+        #[test]
+        fn it_selects_untagged_values() {
+            assert_eq!(select("foo = 4", &["default"]), Some("4".into()));
+        }
+
+        #[test]
+        fn it_selects_commands() {
+            assert_eq!(
+                select("foo = {cmd = 'echo 4'}", &["default"]),
+                Some(TargetValue::Command("echo 4".to_string()))
+            );
+        }
+
+        #[test]
+        fn it_selects_tagged_commands() {
+            assert_eq!(
+                select(
+                    "foo = {default = 4, prod = {cmd = 'echo 4'}}",
+                    &["prod", "default"]
+                ),
+                Some(TargetValue::Command("echo 4".to_string()))
+            );
+        }
+
+        #[test]
+        fn it_selects_table_commands() {
+            assert_eq!(
+                select("[foo]\ncmd = 'echo 4'", &["default"]),
+                Some(TargetValue::Command("echo 4".to_string()))
+            );
+        }
+
+        #[test]
+        fn it_selects_templates() {
+            assert_eq!(
+                select(
+                    "foo = {prod = {template = 'DB={{URL}}'}, default = {template-file = 'db'}}",
+                    &["prod", "default"]
+                ),
+                Some(TargetValue::Template("DB={{URL}}".to_string()))
+            );
+            assert_eq!(
+                select("foo = {template-file = 'db'}", &["default"]),
+                Some(TargetValue::TemplateFile("db".to_string()))
+            );
+        }
+
+        #[test]
+        fn it_selects_unsets() {
+            assert_eq!(
+                select(
+                    "foo = {default = 4, ci = {unset = true}}",
+                    &["ci", "default"]
+                ),
+                Some(TargetValue::Unset)
+            );
+        }
+
+        #[test]
+        fn it_skips_missing_tags() {
+            assert_eq!(select("foo = {prod = 4}", &["default"]), None);
+        }
+
+        #[test]
+        fn it_reports_the_selected_tag() {
+            let document = "foo = {default = 4, prod = 5}\nbar = 6"
+                .parse::<Document>()
+                .expect("invalid doc");
+            let tags = ["prod", "default"];
+            assert_eq!(
+                TargetValue::select_tagged(&document["foo"], &tags),
+                Some(("5".into(), Some("prod")))
+            );
+            assert_eq!(
+                TargetValue::select_tagged(&document["bar"], &tags),
+                Some(("6".into(), None))
+            );
+        }
+    }
+
+    mod unset_keys {
+        use super::super::*;
+        use pretty_assertions::assert_eq;
+        use toml_edit::Document;
+
+        #[test]
+        fn it_reads_untagged_and_tagged_keys() {
+            let toml = r#"
+            [unset]
+            env = ["FOO", "BAR"]
+            alias = {ci = ["g"]}
+            "#;
+            let document = toml.parse::<Document>().expect("invalid doc");
+            let unset = &document["unset"];
+            assert_eq!(unset_keys(&unset["env"], &["default"]), vec!["FOO", "BAR"]);
+            assert_eq!(
+                unset_keys(&unset["alias"], &["default"]),
+                Vec::<&str>::new()
+            );
+            assert_eq!(unset_keys(&unset["alias"], &["ci", "default"]), vec!["g"]);
+        }
+    }
+
+    mod path_entries {
+        use super::super::*;
+        use pretty_assertions::assert_eq;
+        use toml_edit::Document;
+
+        #[test]
+        fn it_reads_path_and_other_variables() {
+            let toml = r#"
+            [path]
+            prepend = ["./bin", "./node_modules/.bin"]
+            PYTHONPATH = {append = "./src"}
+            "#;
+            let document = toml.parse::<Document>().expect("invalid doc");
+            let origin = Path::new("a.toml");
+            let result = PathEntries::from_table(origin, document["path"].as_table().unwrap());
+            assert_eq!(
+                result,
+                vec![
+                    PathEntries {
+                        origin,
+                        var: "PATH",
+                        position: PathPosition::Prepend,
+                        entries: vec!["./bin".to_string(), "./node_modules/.bin".to_string()],
+                    },
+                    PathEntries {
+                        origin,
+                        var: "PYTHONPATH",
+                        position: PathPosition::Append,
+                        entries: vec!["./src".to_string()],
+                    },
+                ]
+            );
+        }
+    }
+}
@@ -28,7 +28,7 @@ fn it_works_when_no_saucefile_exists() {
     let shell_kind = Zsh {};
     context.execute(&shell_kind, false, &mut output);
     assert_eq!(out.value(), "");
-    assert_eq!(err.value(), format!("No saucefiles exist\n"));
+    assert_eq!(err.value(), "No saucefiles exist\n");
 }
 
 #[test]
@@ -47,17 +47,30 @@ fn it_runs() {
         true
     );
 
+    let state = format!(
+        r#"export SAUCE_LOADED='paths = ["{}"]
+vars = [{{ name = "TEST", digest = "50d858e0985ecc7f60418aaf0cc5ab58" }}]
+aliases = ["foo"]
+functions = ["meow"]';"#,
+        mkpath("./tests/execute_it_runs.toml").display()
+    );
     assert_eq!(
         out.value(),
-        r#"export TEST=example;
+        r#"if [ -z "${_sauce_tracked_var_TEST+x}" ]; then _sauce_tracked_var_TEST=1; if [ -n "${TEST+x}" ]; then _sauce_saved_var_TEST=$TEST; fi; fi;
+export TEST=example;
 
+if [ -z "${_sauce_tracked_alias_foo+x}" ]; then _sauce_tracked_alias_foo=1; if alias foo >/dev/null 2>&1; then _sauce_saved_alias_foo="$(alias -L foo)"; fi; fi;
 alias foo=git;
 
+if [ -z "${_sauce_tracked_function_meow+x}" ]; then _sauce_tracked_function_meow=1; if (( ${+functions[meow]} )); then _sauce_saved_function_meow="$(functions meow)"; fi; fi;
 function meow {
   echo "$@"
 };
 
 "#
+        .to_string()
+            + &state
+            + "\n\n"
     );
 }
 
@@ -154,7 +167,7 @@ fn it_moves_correct_file_during_mv_command() {
 
     context.move_saucefile(&mut output, Path::new("./src"), true);
 
-    let expected_result = format!("Moved /.local/share.toml to /.local/share/src.toml\n");
+    let expected_result = "Moved /.local/share.toml to /.local/share/src.toml\n";
 
     assert_eq!(err.value(), expected_result);
 }
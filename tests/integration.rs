@@ -4,7 +4,7 @@ use indoc::indoc;
 #[test]
 fn it_emits_shell_init_content() {
     let mut cmd = Command::cargo_bin("sauce").unwrap();
-    let assert = cmd.args(&["--shell", "bash", "shell", "init"]).assert();
+    let assert = cmd.args(["--shell", "bash", "shell", "init"]).assert();
     assert
         .success()
         .stdout(predicates::str::contains("sauce --shell bash"));
@@ -13,14 +13,14 @@ fn it_emits_shell_init_content() {
 #[test]
 fn it_runs_sauce() {
     let mut cmd = Command::cargo_bin("sauce").unwrap();
-    let assert = cmd.args(&["--shell", "bash"]).assert();
+    let assert = cmd.args(["--shell", "bash"]).assert();
     assert.success();
 }
 
 #[test]
 fn it_runs_sauce_in_show_mode() {
     let mut cmd = Command::cargo_bin("sauce").unwrap();
-    let assert = cmd.args(&["--shell", "bash", "--show"]).assert();
+    let assert = cmd.args(["--shell", "bash", "--show"]).assert();
     assert.success();
 }
 
@@ -28,7 +28,7 @@ fn it_runs_sauce_in_show_mode() {
 fn it_runs_sauce_show_env() {
     let mut cmd = Command::cargo_bin("sauce").unwrap();
     let assert = cmd
-        .args(&[
+        .args([
             "--shell=bash",
             "--file=tests/execute_it_runs.toml",
             "show",
@@ -50,7 +50,7 @@ fn it_runs_sauce_show_env() {
 fn it_runs_shell_exec() {
     let mut cmd = Command::cargo_bin("sauce").unwrap();
     let assert = cmd
-        .args(&["--shell", "bash", "shell", "exec", "'echo foo'"])
+        .args(["--shell", "bash", "shell", "exec", "'echo foo'"])
         .assert();
     assert.success().stderr(predicates::str::contains("foo"));
 }
@@ -59,7 +59,7 @@ fn it_runs_shell_exec() {
 fn it_loads_settings() {
     let mut cmd = Command::cargo_bin("sauce").unwrap();
     let assert = cmd
-        .args(&[
+        .args([
             "--shell",
             "bash",
             "--file=tests/settings.toml",
@@ -70,3 +70,163 @@ fn it_loads_settings() {
 
     assert.success().stderr(predicates::str::contains("foo"));
 }
+
+#[test]
+fn it_shows_unset_without_writing() {
+    let mut cmd = Command::cargo_bin("sauce").unwrap();
+    let assert = cmd
+        .args([
+            "--shell=bash",
+            "--show",
+            "--file=tests/execute_it_runs.toml",
+            "unset",
+            "env",
+            "TEST",
+            "MISSING",
+        ])
+        .assert();
+    assert
+        .success()
+        .stderr("Removing TEST\nNo value found for MISSING\n");
+
+    let content = std::fs::read_to_string("tests/execute_it_runs.toml").unwrap();
+    assert!(content.contains("TEST"));
+}
+
+#[test]
+fn it_refuses_to_import_existing_values() {
+    let mut cmd = Command::cargo_bin("sauce").unwrap();
+    let assert = cmd
+        .args([
+            "--shell=bash",
+            "--show",
+            "--file=tests/execute_it_runs.toml",
+            "import",
+            "tests/import.env",
+        ])
+        .assert();
+    assert
+        .failure()
+        .code(1)
+        .stderr("Refusing to overwrite TEST, use --overwrite or --skip to import anyway\n");
+}
+
+#[test]
+fn it_imports_skipping_existing_values() {
+    let mut cmd = Command::cargo_bin("sauce").unwrap();
+    let assert = cmd
+        .args([
+            "--shell=bash",
+            "--show",
+            "--file=tests/execute_it_runs.toml",
+            "import",
+            "--skip",
+            "tests/import.env",
+        ])
+        .assert();
+    assert
+        .success()
+        .stderr("Skipping existing TEST\nSetting NEW = new\nSetting GREETING = hi $${NAME}\n");
+}
+
+#[test]
+fn it_imports_values_for_a_tag_without_conflict() {
+    let mut cmd = Command::cargo_bin("sauce").unwrap();
+    let assert = cmd
+        .args([
+            "--shell=bash",
+            "--show",
+            "--as=prod",
+            "--file=tests/execute_it_runs.toml",
+            "import",
+            "tests/import.env",
+        ])
+        .assert();
+    assert.success().stderr(
+        "Setting TEST (prod) = other\nSetting NEW (prod) = new\nSetting GREETING (prod) = hi $${NAME}\n",
+    );
+}
+
+#[test]
+fn it_execs_with_the_sauced_environment() {
+    let mut cmd = Command::cargo_bin("sauce").unwrap();
+    let assert = cmd
+        .args([
+            "--shell=bash",
+            "--file=tests/execute_it_runs.toml",
+            "exec",
+            "--",
+            "sh",
+            "-c",
+            "echo $TEST; exit 7",
+        ])
+        .assert();
+    assert.failure().code(7).stdout("example\n");
+}
+
+#[test]
+fn it_passes_exec_through_the_shell_wrapper() {
+    let binary = assert_cmd::cargo::cargo_bin("sauce");
+    let path = format!(
+        "{}:{}",
+        binary.parent().unwrap().display(),
+        std::env::var("PATH").unwrap_or_default()
+    );
+    let init = Command::cargo_bin("sauce")
+        .unwrap()
+        .args(["--shell", "bash", "shell", "init"])
+        .output()
+        .unwrap()
+        .stdout;
+
+    // Were the child's output evaluated, `echo pwned` would print `pwned`.
+    let script = format!(
+        "{}\nsauce --as prod --file tests/execute_it_runs.toml exec -- echo 'echo pwned'",
+        String::from_utf8(init).unwrap()
+    );
+    let mut cmd = Command::new("bash");
+    let assert = cmd.args(["-c", &script]).env("PATH", path).assert();
+    assert.success().stdout("echo pwned\n");
+}
+
+#[test]
+fn it_exports_to_stdout() {
+    let mut cmd = Command::cargo_bin("sauce").unwrap();
+    let assert = cmd
+        .args([
+            "--shell=bash",
+            "--file=tests/execute_it_runs.toml",
+            "export",
+            "--format=json",
+        ])
+        .assert();
+    assert
+        .success()
+        .stdout("{\n  \"TEST\": \"example\"\n}\n")
+        .stderr("Skipped foo, meow, which can't be represented in the given format\n");
+}
+
+#[test]
+fn it_runs_functions_as_tasks() {
+    let mut cmd = Command::cargo_bin("sauce").unwrap();
+    let assert = cmd
+        .args([
+            "--shell=bash",
+            "--file=tests/run.toml",
+            "run",
+            "greet",
+            "a",
+            "b c",
+        ])
+        .assert();
+    assert.failure().code(3).stdout("hi 2: a b c\n");
+}
+
+#[test]
+fn it_lists_functions_to_run() {
+    let mut cmd = Command::cargo_bin("sauce").unwrap();
+    let assert = cmd
+        .args(["--shell=bash", "--file=tests/run.toml", "run", "--list"])
+        .assert();
+    assert.success().stdout("greet\n");
+}
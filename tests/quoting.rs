@@ -0,0 +1,134 @@
+//! Round-trips hostile values through each shell which is installed, asserting that
+//! what the shell ends up with is exactly the original value.
+use std::process::Command;
+
+use sauce::shell::{Bash, Fish, Nushell, PowerShell, Shell, Zsh};
+
+const VALUES: &[&str] = &[
+    "",
+    "plain",
+    "with spaces",
+    "it's",
+    "\"double\"",
+    "'\"mixed'\"",
+    "$(echo injected)",
+    "`echo injected`",
+    "${HOME}",
+    "$HOME",
+    "back\\slash",
+    "trailing\\",
+    "\\n",
+    "\\'",
+    "new\nline",
+    "trailing newline\n",
+    "tab\there",
+    "!bang !!",
+    "%s %d",
+    "~",
+    "=cmd",
+    "*glob?[a]",
+    "a;b|c&d>e<f",
+    "{brace,expansion}",
+    "# comment",
+    "-n",
+    "unicode ✓ é 日本 \u{2019}",
+];
+
+/// A shell, the command with which it runs a script, how it prints (`{}`) a word,
+/// and how it refers to the `SAUCE_VALUE` env var.
+struct Case {
+    shell: Box<dyn Shell>,
+    command: &'static [&'static str],
+    print: &'static str,
+    var: &'static str,
+}
+
+impl Case {
+    fn print(&self, word: &str) -> String {
+        self.print.replace("{}", word)
+    }
+}
+
+fn cases() -> Vec<Case> {
+    vec![
+        Case {
+            shell: Box::new(Bash {}),
+            command: &["bash", "-c"],
+            print: "printf '%s' {}",
+            var: "\"$SAUCE_VALUE\"",
+        },
+        Case {
+            shell: Box::new(Zsh {}),
+            command: &["zsh", "-f", "-c"],
+            print: "printf '%s' {}",
+            var: "\"$SAUCE_VALUE\"",
+        },
+        Case {
+            shell: Box::new(Fish {}),
+            command: &["fish", "--no-config", "-c"],
+            print: "printf '%s' {}",
+            var: "\"$SAUCE_VALUE\"",
+        },
+        Case {
+            shell: Box::new(PowerShell {}),
+            command: &["pwsh", "-NoProfile", "-NonInteractive", "-c"],
+            print: "[Console]::Out.Write({})",
+            var: "$env:SAUCE_VALUE",
+        },
+        // Nushell's own output is applied by `init`'s wrapper, so the scripts it
+        // runs itself (i.e. for `sauce run`) are what's round-tripped.
+        Case {
+            shell: Nushell {}.script().unwrap(),
+            command: &["nu", "--no-config-file", "-c"],
+            print: "print -n {}",
+            var: "$env.SAUCE_VALUE",
+        },
+    ]
+}
+
+/// Runs `script`, returning its stdout, or `None` if the shell isn't installed.
+fn run(command: &[&str], script: &str) -> Option<String> {
+    let output = Command::new(command[0])
+        .args(&command[1..])
+        .arg(script)
+        .env_remove("SAUCE_VALUE")
+        .output()
+        .ok()?;
+    assert!(
+        output.status.success(),
+        "{} failed on:\n{}\n{}",
+        command[0],
+        script,
+        String::from_utf8_lossy(&output.stderr)
+    );
+    Some(String::from_utf8(output.stdout).unwrap())
+}
+
+/// Asserts that each script produced from `script` outputs the value it was given.
+fn assert_round_trips(script: impl Fn(&Case, &str) -> String) {
+    for case in cases() {
+        for value in VALUES {
+            let script = script(&case, value);
+            match run(case.command, &script) {
+                Some(result) => assert_eq!(&result, value, "{}: {}", case.shell.name(), script),
+                None => {
+                    eprintln!("Skipping {}, which isn't installed", case.command[0]);
+                    break;
+                }
+            }
+        }
+    }
+}
+
+#[test]
+fn it_round_trips_vars() {
+    assert_round_trips(|case, value| {
+        let set = case.shell.set_var("SAUCE_VALUE", value);
+        format!("{};\n{}", set, case.print(case.var))
+    });
+}
+
+#[test]
+fn it_round_trips_quoted_words() {
+    assert_round_trips(|case, value| case.print(&case.shell.quote(value)));
+}